@@ -0,0 +1,98 @@
+// Scans fake code for a few signatures, with `Scanner` and with `Signature::scan` one by one.
+//
+//      cargo bench --target x86_64-unknown-linux-gnu --bench scanner
+
+// We only use some of each module, and their tests only build with the test harness.
+#![allow(dead_code, unused_imports)]
+
+#[path = "../src/scanner.rs"]
+mod scanner;
+
+#[path = "../src/signature.rs"]
+mod signature;
+
+use scanner::Scanner;
+use signature::Signature;
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const LEN: usize = 32 << 20;
+const RUNS: u32 = 5;
+
+// Mostly common MSVC bytes, like a real code section.
+fn memory() -> Vec<u8> {
+    const COMMON: [u8; 16] = [0x00, 0xFF, 0x8B, 0xCC, 0x24, 0x89, 0x44, 0x45, 0x04, 0x08, 0x0F, 0x85, 0x83, 0xE8, 0x01, 0x10];
+    let mut state = 0x9E37_79B9_u32;
+
+    (0..LEN)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+
+            #[allow(clippy::cast_possible_truncation)]
+            if state & 3 == 0 { state as u8 } else { COMMON[state as usize % COMMON.len()] }
+        })
+        .collect()
+}
+
+fn best_of(f: impl Fn()) -> Duration {
+    (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            f();
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn report(name: &str, time: Duration) {
+    #[allow(clippy::cast_precision_loss)]
+    let throughput = LEN as f64 / time.as_secs_f64() / f64::from(1 << 20);
+    println!("{:<40} {:>10.2?} {:>10.0} MiB/s", name, time, throughput);
+}
+
+fn main() {
+    let memory = memory();
+
+    // None of these are in the memory, so every scan reads all of it.
+    let signatures = [
+        Signature::parse("68 ?? ?? ?? ?? E8 ?? ?? ?? ?? 6A 07 68").unwrap(),
+        Signature::parse("8B 35 ?? ?? ?? ?? 56 57 C3").unwrap(),
+        Signature::parse("FF 15 ?? ?? ?? ?? 5E 5D 5B C2 10 00").unwrap(),
+        Signature::parse("55 8B EC 83 E4 F8 81 EC ?? ?? ?? ?? A1").unwrap(),
+        Signature::parse("E8 | ?? ?? ?? ?? 8? 4? 24 7F 3C").unwrap(),
+    ];
+
+    let references = signatures.iter().collect::<Vec<_>>();
+
+    report("Signature::scan, 1 signature", best_of(|| {
+        black_box(signatures[0].scan(black_box(&memory)));
+    }));
+
+    report("Scanner, 1 signature (memchr)", best_of(|| {
+        black_box(Scanner::new(&references[..1]).first(black_box(&memory)));
+    }));
+
+    report("Signature::scan, 3 signatures", best_of(|| {
+        for signature in &signatures[..3] {
+            black_box(signature.scan(black_box(&memory)));
+        }
+    }));
+
+    report("Scanner, 3 signatures (memchr3)", best_of(|| {
+        black_box(Scanner::new(&references[..3]).first(black_box(&memory)));
+    }));
+
+    report("Signature::scan, 5 signatures", best_of(|| {
+        for signature in &signatures {
+            black_box(signature.scan(black_box(&memory)));
+        }
+    }));
+
+    report("Scanner, 5 signatures (byte by byte)", best_of(|| {
+        black_box(Scanner::new(&references).first(black_box(&memory)));
+    }));
+}
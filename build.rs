@@ -1,5 +1,7 @@
 use std::env;
-use std::path::PathBuf;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
 
 use bindgen::{Builder, CargoCallbacks, EnumVariation};
 
@@ -7,6 +9,11 @@ fn main() {
     // Tell cargo to invalidate the built crate whenever the wrapper changes
     println!("cargo:rerun-if-changed=wrapper.hpp");
 
+    // The game only runs on Windows. Elsewhere, we only build the parts that don't touch the game, for the tests.
+    if env::var("CARGO_CFG_TARGET_OS").map_or(true, |os| os != "windows") {
+        return;
+    }
+
     // The bindgen::Builder is the main entry point
     // to bindgen, and lets you build up options for
     // the resulting bindings.
@@ -53,4 +60,187 @@ fn main() {
     bindings
         .write_to_file(out_path.join("sdk.rs"))
         .expect("Couldn't write bindings!");
+
+    generate_surface_vtable(&out_path);
+}
+
+// Writes $OUT_DIR/surface_vtable.rs, a `vtable!` block for every method in surface_virtual_funcs.txt.
+fn generate_surface_vtable(out_path: &Path) {
+    const DECLARATIONS: &str = "surface_virtual_funcs.txt";
+
+    println!("cargo:rerun-if-changed={}", DECLARATIONS);
+
+    let text = fs::read_to_string(DECLARATIONS).expect("Couldn't read the Surface declarations!");
+
+    let declarations: Vec<Declaration> = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(Declaration::parse)
+        .collect();
+
+    let mut code = String::from("// Generated by build.rs from surface_virtual_funcs.txt.\n\n");
+    code.push_str("impl Surface {\n    vtable! {\n        size = NUM_VTABLE_ENTRIES;\n\n");
+
+    for (index, (declaration, overload)) in vtable_order(&declarations).into_iter().enumerate() {
+        if declaration.is_destructor {
+            let _ = writeln!(code, "        // {} {}()", index, declaration.name);
+            continue;
+        }
+
+        let mut name = snake_case(&declaration.name);
+
+        // Overloads after the first are named after their parameter types, e.g. draw_set_color_color.
+        if overload > 0 {
+            for parameter in &declaration.parameters {
+                name.push('_');
+                name.push_str(&snake_case(&parameter.c_type.replace(|c: char| !c.is_alphanumeric(), "")));
+            }
+        }
+
+        let parameters: Vec<String> = declaration.parameters
+            .iter()
+            .map(|parameter| format!("{}: {}", parameter.name, rust_type(&parameter.c_type)))
+            .collect();
+
+        let _ = write!(code, "        {} pub {}({})", index, name, parameters.join(", "));
+
+        if declaration.return_type != "void" {
+            let _ = write!(code, " -> {}", rust_type(&declaration.return_type));
+        }
+
+        code.push_str(",\n");
+    }
+
+    code.push_str("    }\n}\n\n");
+    let _ = writeln!(code, "pub const NUM_GENERATED_ENTRIES: usize = {};", declarations.len());
+
+    fs::write(out_path.join("surface_vtable.rs"), code).expect("Couldn't write the Surface vtable!");
+}
+
+struct Parameter {
+    c_type: String,
+    name: String,
+}
+
+struct Declaration {
+    return_type: String,
+    name: String,
+    parameters: Vec<Parameter>,
+    is_destructor: bool,
+}
+
+impl Declaration {
+    // virtual void DrawSetColor( int r , int g , int b , int a ) = 0;
+    fn parse(line: &str) -> Declaration {
+        let line = line.strip_prefix("virtual").unwrap_or_else(|| panic!("Not a virtual function: {}", line));
+        let open = line.find('(').unwrap_or_else(|| panic!("No parameter list: {}", line));
+        let close = line.rfind(')').unwrap_or_else(|| panic!("No parameter list: {}", line));
+
+        let (return_type, name) = split_declarator(&line[..open]);
+
+        let parameters = line[open + 1..close]
+            .split(',')
+            .map(|parameter| parameter.split('=').next().unwrap().trim())
+            .filter(|parameter| !parameter.is_empty() && *parameter != "void")
+            .map(|parameter| {
+                let (c_type, name) = split_declarator(parameter);
+                Parameter { c_type, name: escape_keyword(snake_case(&name)) }
+            })
+            .collect();
+
+        Declaration {
+            is_destructor: name.starts_with('~'),
+            return_type,
+            name,
+            parameters,
+        }
+    }
+}
+
+// Splits "const char *filename" into ("const char *", "filename").
+fn split_declarator(declarator: &str) -> (String, String) {
+    let declarator = declarator.trim();
+    let name_start = declarator
+        .rfind(|c: char| !(c.is_alphanumeric() || c == '_' || c == '~'))
+        .map_or(0, |i| i + 1);
+
+    let c_type = declarator[..name_start].trim().to_string();
+    let name = declarator[name_start..].to_string();
+    (c_type, name)
+}
+
+fn escape_keyword(name: String) -> String {
+    const KEYWORDS: [&str; 8] = ["box", "fn", "in", "loop", "move", "ref", "type", "use"];
+
+    if KEYWORDS.contains(&name.as_str()) {
+        name + "_"
+    } else {
+        name
+    }
+}
+
+// MSVC groups overloads of a virtual function at the first overload's position,
+// and puts the group in reverse declaration order.
+// So DrawSetColor(Color) comes before DrawSetColor(int, int, int, int) in the vtable.
+// Each entry comes with its position in declaration order among its overloads.
+fn vtable_order(declarations: &[Declaration]) -> Vec<(&Declaration, usize)> {
+    let mut groups: Vec<Vec<&Declaration>> = vec![];
+
+    for declaration in declarations {
+        match groups.iter_mut().find(|group| group[0].name == declaration.name) {
+            Some(group) => group.push(declaration),
+            None => groups.push(vec![declaration]),
+        }
+    }
+
+    groups
+        .into_iter()
+        .flat_map(|group| group.into_iter().enumerate().rev())
+        .map(|(overload, declaration)| (declaration, overload))
+        .collect()
+}
+
+fn snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut snake = String::new();
+
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            let previous = chars[i - 1];
+            let next = chars.get(i + 1).copied().unwrap_or('_');
+
+            if previous.is_lowercase() || previous.is_numeric() || (previous.is_uppercase() && next.is_lowercase()) {
+                snake.push('_');
+            }
+        }
+
+        snake.extend(c.to_lowercase());
+    }
+
+    snake
+}
+
+fn rust_type(c_type: &str) -> String {
+    let is_const = c_type.starts_with("const ");
+    let indirection = c_type.matches(&['*', '&'][..]).count();
+
+    let base = c_type
+        .trim_start_matches("const ")
+        .trim_end_matches(|c: char| c == '*' || c == '&' || c.is_whitespace());
+
+    let base = match base {
+        "bool" => "bool",
+        "int" | "SurfaceFeature_e" => "i32",
+        "unsigned char" => "u8",
+        "char" => "c_char",
+        "wchar_t" => "wchar_t",
+        "Color" | "HCursor" | "HFont" | "HTexture" => base,
+        "IPanel" => "Panel",
+        "IHTML" | "IHTMLEvents" => "c_void",
+        _ => panic!("No Rust type for the C++ type \"{}\"", c_type),
+    };
+
+    let pointer = if is_const { "*const " } else { "*mut " };
+    pointer.repeat(indirection) + base
 }
\ No newline at end of file
@@ -0,0 +1,164 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Error, FnArg, Ident, ItemFn, Pat, Path, ReturnType, Token};
+use syn::parse::{Parse, ParseStream, Result};
+use syn::punctuated::Pair;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    // Our function runs, then the original.
+    Pre,
+
+    // The original runs, then our function.
+    Post,
+
+    // Our function runs instead of the original. It can still call `original`.
+    Replace,
+}
+
+// cl_clientfuncs_s::HUD_Frame, pre
+struct Settings {
+    table: Path,
+    field: Ident,
+    mode: Mode,
+}
+
+impl Parse for Settings {
+    fn parse(input: ParseStream) -> Result<Self> {
+        // cl_clientfuncs_s::HUD_Frame
+        let mut table: Path = input.parse()?;
+
+        let field = match table.segments.pop() {
+            Some(pair) if !table.segments.is_empty() => pair.into_value().ident,
+            _ => return Err(Error::new_spanned(table, "Expected a table and a field, e.g. cl_clientfuncs_s::HUD_Frame.")),
+        };
+
+        // The path still ends with the separator that came before the field.
+        let table = Path {
+            leading_colon: table.leading_colon,
+            segments: table.segments.into_pairs().map(Pair::into_value).collect(),
+        };
+
+        let mut settings = Settings {
+            table,
+            field,
+            mode: Mode::Post,
+        };
+
+        while !input.is_empty() {
+            input.parse::<Token![,]>()?;
+
+            if input.is_empty() {
+                break;
+            }
+
+            let option: Ident = input.parse()?;
+
+            match option.to_string().as_str() {
+                "pre" => settings.mode = Mode::Pre,
+                "post" => settings.mode = Mode::Post,
+                "replace" => settings.mode = Mode::Replace,
+                _ => return Err(Error::new_spanned(option, "Expected pre, post, or replace.")),
+            }
+        }
+
+        Ok(settings)
+    }
+}
+
+pub fn expand(settings: TokenStream, function: TokenStream) -> Result<TokenStream> {
+    let Settings { table, field, mode } = syn::parse2(settings)?;
+    let ItemFn { attrs, vis, sig, block } = syn::parse2(function)?;
+
+    let name = &sig.ident;
+    let inputs = &sig.inputs;
+    let output = &sig.output;
+
+    let args = inputs
+        .iter()
+        .map(|arg| match arg {
+            FnArg::Typed(pattern_type) => match &*pattern_type.pat {
+                Pat::Ident(p) => Ok(&p.ident),
+                _ => Err(Error::new_spanned(arg, "Hooked functions need plain argument names.")),
+            },
+
+            FnArg::Receiver(_) => Err(Error::new_spanned(arg, "Hooked functions can't take self.")),
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let types: Vec<_> = inputs
+        .iter()
+        .filter_map(|arg| match arg {
+            FnArg::Typed(pattern_type) => Some(&pattern_type.ty),
+            FnArg::Receiver(_) => None,
+        })
+        .collect();
+
+    let return_type = match output {
+        ReturnType::Default => quote! { () },
+        ReturnType::Type(_, ty) => quote! { #ty },
+    };
+
+    // Only a replacement decides what the hook returns.
+    let body_type = if mode == Mode::Replace {
+        quote! { -> #return_type }
+    } else {
+        quote! {}
+    };
+
+    let call_original = quote! { original(#(#args),*) };
+
+    // The body runs in a closure so that a `return` in it can't skip the original.
+    // The guard skips the body when the hook is disabled, and contains its panics in debug builds.
+    let run_body = quote! { crate::guard::run(&STATE, move || #body_type #block) };
+
+    let run = match mode {
+        Mode::Pre => quote! {
+            #run_body;
+            #call_original
+        },
+
+        Mode::Post => quote! {
+            let result = #call_original;
+            #run_body;
+            result
+        },
+
+        // The body may call the original before it panics. Then we return what the original returned
+        // instead of running the original a second time.
+        Mode::Replace => quote! {
+            let returned = core::cell::Cell::new(None);
+
+            let original = |#(#args: #types),*| -> #return_type {
+                let value = #call_original;
+                returned.set(Some(value));
+                value
+            };
+
+            match #run_body {
+                Some(value) => value,
+                None => match returned.get() {
+                    Some(value) => value,
+                    None => #call_original,
+                },
+            }
+        },
+    };
+
+    Ok(quote! {
+        #(#attrs)*
+        #vis unsafe extern "C" fn #name(#inputs) #output {
+            static STATE: crate::guard::HookState =
+                crate::guard::HookState::new(concat!(stringify!(#table), "::", stringify!(#field)));
+
+            crate::single_thread_verifier::assert();
+
+            #[allow(unused_variables)]
+            let original: unsafe extern "C" fn(#(#types),*) -> #return_type = crate::yank::Yank::yank(
+                <#table as crate::hook::FunctionTable>::originals().#field
+            );
+
+            #run
+        }
+    })
+}
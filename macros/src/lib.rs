@@ -1,28 +1,83 @@
 use proc_macro::TokenStream as OldTokenStream;
 
 use proc_macro2::TokenStream;
-use quote::{quote, ToTokens};
-use syn::{Error, Expr, FnArg, Ident, parenthesized, parse_macro_input, Pat, Token, Type, Visibility};
+use quote::{format_ident, quote, ToTokens};
+use syn::{Attribute, Error, Expr, ExprLit, FnArg, Ident, Lit, parenthesized, parse_macro_input, Pat, PatType, Token, Type, Visibility};
 use syn::parse::{Parse, ParseStream, Result};
 use syn::punctuated::Punctuated;
 
+mod hook;
+
+#[derive(Clone, Copy)]
 enum FunctionKind {
     Regular,
     Virtual,
 }
 
+#[derive(Clone, Copy, PartialEq)]
+enum Convention {
+    Thiscall,
+    Stdcall,
+    Cdecl,
+    Fastcall,
+    System,
+}
+
+impl Parse for Convention {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let name: Ident = input.parse()?;
+
+        Ok(match name.to_string().as_str() {
+            "thiscall" => Convention::Thiscall,
+            "stdcall" => Convention::Stdcall,
+            "cdecl" => Convention::Cdecl,
+            "fastcall" => Convention::Fastcall,
+            "system" => Convention::System,
+            _ => return Err(Error::new_spanned(name, "Expected thiscall, stdcall, cdecl, fastcall, or system.")),
+        })
+    }
+}
+
+struct Arg {
+    name: Ident,
+    ty: Type,
+}
+
 struct Function {
+    // Doc comments, which we pass through to the method.
+    docs: Vec<Attribute>,
+
     index: Expr,
     visibility: Visibility,
     name: Ident,
-    args: Punctuated<FnArg, Token![,]>,
+    args: Vec<Arg>,
+    variadic: bool,
     return_type: Option<Type>,
     kind: FunctionKind,
+
+    // None means the default of the block, or else of the kind.
+    convention: Option<Convention>,
+
+    // Statements that go before the call, e.g. compile-time checks.
+    prologue: TokenStream,
 }
 
 impl Parse for Function {
     fn parse(input: ParseStream) -> Result<Self> {
-        // 51 pub get_local_player() -> *const Entity
+        // #[cdecl] 51 pub get_local_player() -> *const Entity
+
+        // /// Prints to the console.
+        // #[cdecl]
+        let mut docs = vec![];
+        let mut convention = None;
+
+        for attribute in input.call(Attribute::parse_outer)? {
+            if attribute.path.is_ident("doc") {
+                docs.push(attribute);
+            } else {
+                convention = Some(syn::parse2(attribute.path.to_token_stream())?);
+            }
+        }
 
         // 51
         let index = input.parse()?;
@@ -34,10 +89,44 @@ impl Parse for Function {
         let name = input.parse()?;
         
         // ()
-        let args;
-        parenthesized!(args in input);
+        let content;
+        parenthesized!(content in input);
+
+        let mut args = vec![];
+        let mut variadic = false;
+
+        while !content.is_empty() {
+            // ...
+            if content.peek(Token![...]) {
+                content.parse::<Token![...]>()?;
+                variadic = true;
+
+                if !content.is_empty() {
+                    return Err(content.error("... must be the last argument."));
+                }
+
+                break;
+            }
+
+            args.push(match content.parse()? {
+                FnArg::Typed(PatType { pat, ty, .. }) => Arg {
+                    // Patterns other than plain names get a generated name, because we need to pass the value along.
+                    name: match *pat {
+                        Pat::Ident(p) => p.ident,
+                        _ => format_ident!("arg{}", args.len()),
+                    },
+                    ty: *ty,
+                },
+
+                receiver @ FnArg::Receiver(_) => return Err(Error::new_spanned(receiver, "Unsupported argument form.")),
+            });
+
+            if content.is_empty() {
+                break;
+            }
 
-        let args = args.parse_terminated(FnArg::parse)?;
+            content.parse::<Token![,]>()?;
+        }
 
         // ->
         let return_type = if input.peek(Token![->]) {
@@ -50,88 +139,207 @@ impl Parse for Function {
         };
 
         Ok(Self {
+            docs,
             index,
             visibility,
             name,
             args,
+            variadic,
             return_type,
             kind: FunctionKind::Regular,
+            convention,
+            prologue: TokenStream::new(),
         })
     }
 }
 
 impl ToTokens for Function {
     fn to_tokens(&self, tokens: &mut TokenStream) {
-        let Function { index, visibility, name, args, return_type, kind } = &self;
-        
-        let arg_names = args
-            .iter()
-            .map(|arg| if let FnArg::Typed(pattern_type) = arg {
-                if let Pat::Ident(p) = &*pattern_type.pat {
-                    p.ident.to_token_stream()
-                } else {
-                    Error::new_spanned(arg, "Unsupported argument form.").to_compile_error()
-                }
-            } else {
-                Error::new_spanned(arg, "Unsupported argument form.").to_compile_error()
-            });
+        let Function { docs, index, visibility, name, args, variadic, return_type, kind, convention, prologue } = &self;
+
+        let arg_names: Vec<&Ident> = args.iter().map(|arg| &arg.name).collect();
+        let arg_types: Vec<&Type> = args.iter().map(|arg| &arg.ty).collect();
+
+        let convention = convention.unwrap_or(match kind {
+            FunctionKind::Regular => Convention::Cdecl,
+            FunctionKind::Virtual => Convention::Thiscall,
+        });
+
+        let abi = match convention {
+            // Rust can't declare thiscall functions, so we pass `this` in ecx with fastcall and pass nothing in edx.
+            Convention::Thiscall | Convention::Fastcall => "fastcall",
+            Convention::Stdcall => "stdcall",
+            Convention::Cdecl => "C",
+            Convention::System => "system",
+        };
+
+        if *variadic && convention != Convention::Cdecl {
+            *tokens = Error::new_spanned(name, "Only cdecl functions can be variadic.").to_compile_error();
+            return;
+        }
+
+        if *variadic && args.is_empty() && matches!(kind, FunctionKind::Regular) {
+            *tokens = Error::new_spanned(name, "A variadic function needs an argument before the `...`.").to_compile_error();
+            return;
+        }
 
         let index = quote! { (#index) as usize };
 
-        let (function_type, address, call) = match kind {
-            FunctionKind::Regular => (
-                quote! { "C" fn(#args) },
+        let (address, this_types, this_values) = match (kind, convention) {
+            (FunctionKind::Regular, Convention::Thiscall) => {
+                *tokens = Error::new_spanned(name, "thiscall needs a this pointer, so only vtable! entries can use it.")
+                    .to_compile_error();
+                return;
+            }
+
+            (FunctionKind::Regular, _) => (
                 quote! { self.functions[#index] },
-                quote! { function(#(#arg_names),*) }
+                quote! {},
+                quote! {},
+            ),
+
+            (FunctionKind::Virtual, Convention::Thiscall) => (
+                quote! { (*self.vtable)[#index] },
+                quote! { usize, usize, },
+                quote! { self as *const _ as usize, 0, },
             ),
 
-            FunctionKind::Virtual => (
-                quote! { "fastcall" fn(this: usize, edx: usize, #args) },
+            (FunctionKind::Virtual, _) => (
                 quote! { (*self.vtable)[#index] },
-                quote! { function(self as *const _ as usize, 0, #(#arg_names),*) }
+                quote! { usize, },
+                quote! { self as *const _ as usize, },
             ),
         };
 
-        let return_type = if let Some(return_type) = return_type {
-            quote! { -> #return_type }
+        *tokens = if *variadic {
+            // Rust can't forward `...`, so the variadic arguments come as one tuple. See src/variadic.rs.
+            let return_type = return_type.as_ref().map_or_else(|| quote! { () }, ToTokens::to_token_stream);
+
+            quote! {
+                #(#docs)*
+                #visibility fn #name<V: crate::variadic::VarArgs<(#this_types #(#arg_types,)*), #return_type>>(
+                    &self, #(#arg_names: #arg_types,)* rest: V) -> #return_type {
+
+                    #prologue
+                    unsafe { rest.call(#address, (#this_values #(#arg_names,)*)) }
+                }
+            }
         } else {
-            quote! {}
-        };
+            let return_type = if let Some(return_type) = return_type {
+                quote! { -> #return_type }
+            } else {
+                quote! {}
+            };
 
-        *tokens = quote! {
-            #visibility fn #name(&self, #args) #return_type {
-                type Function = extern #function_type #return_type;
-                let function = unsafe { core::mem::transmute::<usize, Function>(#address) };
-                #call
+            quote! {
+                #(#docs)*
+                #visibility fn #name(&self, #(#arg_names: #arg_types),*) #return_type {
+                    #prologue
+                    type Function = extern #abi fn(#this_types #(#arg_types),*) #return_type;
+                    let function = unsafe { core::mem::transmute::<usize, Function>(#address) };
+                    function(#this_values #(#arg_names),*)
+                }
             }
         };
     }
 }
 
+mod keyword {
+    syn::custom_keyword!(convention);
+    syn::custom_keyword!(entries);
+    syn::custom_keyword!(size);
+}
+
+// Settings that come before the entries.
+//
+//      size = NUM_VTABLE_ENTRIES;
+//      convention = stdcall;
+//      entries = PAINT_ENTRIES;
+struct Header {
+    size: Option<Expr>,
+    convention: Option<Convention>,
+
+    // The name of the const that lists the entries, so that two vtable! blocks can share an impl.
+    entries: Option<Ident>,
+}
+
+impl Parse for Header {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut header = Header {
+            size: None,
+            convention: None,
+            entries: None,
+        };
+
+        loop {
+            if input.peek(keyword::size) && input.peek2(Token![=]) {
+                input.parse::<keyword::size>()?;
+                input.parse::<Token![=]>()?;
+                header.size = Some(input.parse()?);
+            } else if input.peek(keyword::convention) && input.peek2(Token![=]) {
+                input.parse::<keyword::convention>()?;
+                input.parse::<Token![=]>()?;
+                header.convention = Some(input.parse()?);
+            } else if input.peek(keyword::entries) && input.peek2(Token![=]) {
+                input.parse::<keyword::entries>()?;
+                input.parse::<Token![=]>()?;
+                header.entries = Some(input.parse()?);
+            } else {
+                break;
+            }
+
+            input.parse::<Token![;]>()?;
+        }
+
+        Ok(header)
+    }
+}
+
+fn parse_entries(input: ParseStream, kind: FunctionKind, convention: Option<Convention>)
+    -> Result<Punctuated<Function, Token![,]>> {
+
+    let mut entries = input.parse_terminated(Function::parse)?;
+
+    for entry in &mut entries {
+        entry.kind = kind;
+        entry.convention = entry.convention.or(convention);
+    }
+
+    Ok(entries)
+}
+
 struct Functions {
     entries: Punctuated<Function, Token![,]>,
 }
 
 impl Parse for Functions {
     fn parse(input: ParseStream) -> Result<Self> {
+        let header: Header = input.parse()?;
+
+        if header.size.is_some() || header.entries.is_some() {
+            return Err(input.error("Only vtable! takes a size and an entries name."));
+        }
+
         Ok(Self {
-            entries: input.parse_terminated(Function::parse)?,
+            entries: parse_entries(input, FunctionKind::Regular, header.convention)?,
         })
     }
 }
 
 struct Vtable {
+    size: Option<Expr>,
+    entries_name: Option<Ident>,
     entries: Punctuated<Function, Token![,]>,
 }
 
 impl Parse for Vtable {
     fn parse(input: ParseStream) -> Result<Self> {
+        let header: Header = input.parse()?;
+
         Ok(Self {
-            entries: input.parse_terminated(|p| {
-                let mut entry = Function::parse(p)?;
-                entry.kind = FunctionKind::Virtual;
-                Ok(entry)
-            })?,
+            size: header.size,
+            entries_name: header.entries,
+            entries: parse_entries(input, FunctionKind::Virtual, header.convention)?,
         })
     }
 }
@@ -148,14 +356,202 @@ pub fn functions(input: OldTokenStream) -> OldTokenStream {
     generated.into()
 }
 
+// The index as a number, if the index is an integer literal.
+fn literal_index(index: &Expr) -> Option<u64> {
+    match index {
+        Expr::Lit(ExprLit { lit: Lit::Int(int), .. }) => int.base10_parse().ok(),
+        _ => None,
+    }
+}
+
+// Two entries with the same literal index. `duplicate_check` catches the rest when the indices are evaluated.
+fn check_duplicates(entries: &Punctuated<Function, Token![,]>) -> Result<()> {
+    let mut seen: Vec<(u64, &Ident)> = vec![];
+
+    for entry in entries {
+        if let Some(index) = literal_index(&entry.index) {
+            if let Some((_, first)) = seen.iter().find(|(seen_index, _)| *seen_index == index) {
+                return Err(Error::new_spanned(
+                    &entry.index,
+                    format!("duplicate vtable index {}; `{}` already uses it", index, first),
+                ));
+            }
+
+            seen.push((index, &entry.name));
+        }
+    }
+
+    Ok(())
+}
+
+// A const item that fails to compile when entry `position` shares its index with another entry.
+fn duplicate_check(entries: &Punctuated<Function, Token![,]>, position: usize) -> TokenStream {
+    let indices = entries.iter().map(|Function { index, .. }| index);
+    let name = &entries[position].name;
+
+    quote! {
+        const _: () = {
+            let indices: &[usize] = &[#((#indices) as usize),*];
+            let mut other = 0;
+
+            while other < indices.len() {
+                assert!(
+                    other == #position || indices[other] != indices[#position],
+                    concat!("`", stringify!(#name), "` has the same vtable index as another entry"),
+                );
+
+                other += 1;
+            }
+        };
+    }
+}
+
 #[proc_macro]
 pub fn vtable(input: OldTokenStream) -> OldTokenStream {
-    let Vtable { entries } = parse_macro_input!(input as Vtable);
-    
+    let Vtable { size, entries_name, mut entries } = parse_macro_input!(input as Vtable);
+
+    if let Err(e) = check_duplicates(&entries) {
+        return e.to_compile_error().into();
+    }
+
+    // Literal indices can't collide with each other, so only the other indices need a check.
+    let duplicate_checks: Vec<TokenStream> = (0..entries.len())
+        .map(|position| if literal_index(&entries[position].index).is_some() {
+            TokenStream::new()
+        } else {
+            duplicate_check(&entries, position)
+        })
+        .collect();
+
+    for (entry, check) in entries.iter_mut().zip(duplicate_checks) {
+        entry.prologue.extend(check);
+    }
+
+    let bound: Vec<TokenStream> = entries
+        .iter()
+        .map(|Function { index, name, .. }| {
+            let name = name.to_string();
+            quote! { ((#index) as usize, #name) }
+        })
+        .collect();
+
+    if let Some(size) = &size {
+        for entry in &mut entries {
+            let Function { index, name, .. } = &entry;
+
+            // This const item fails to compile when the index is out of range.
+            entry.prologue.extend(quote! {
+                const _: () = assert!(
+                    (#index) < (#size),
+                    concat!("`", stringify!(#name), "` has vtable index ", stringify!(#index),
+                            ", which is out of range for a vtable of ", stringify!(#size), " entries"),
+                );
+            });
+        }
+    }
+
     let generated: TokenStream = entries
         .into_iter()
         .map(ToTokens::into_token_stream)
         .collect();
 
+    let entries_name = entries_name.unwrap_or_else(|| format_ident!("VTABLE_ENTRIES"));
+
+    let generated = quote! {
+        // Each bound entry's index and name, for logging.
+        pub const #entries_name: &'static [(usize, &'static str)] = &[#(#bound),*];
+
+        #generated
+    };
+
     generated.into()
-}
\ No newline at end of file
+}
+
+// Turns a function into the detour of a game function. See hook.rs.
+//
+//      #[hook(cl_clientfuncs_s::HUD_Frame, post)]
+//      fn my_hud_frame(time: f64) { ... }
+#[proc_macro_attribute]
+pub fn hook(settings: OldTokenStream, function: OldTokenStream) -> OldTokenStream {
+    hook::expand(settings.into(), function.into())
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Function, FunctionKind, Vtable};
+    use proc_macro2::TokenStream;
+    use quote::{quote, ToTokens};
+
+    fn expand(entry: TokenStream, kind: FunctionKind) -> String {
+        let mut function: Function = syn::parse2(entry).unwrap();
+        function.kind = kind;
+        function.into_token_stream().to_string()
+    }
+
+    #[test]
+    fn expands_a_thiscall_entry() {
+        let expanded = expand(quote! { 36 pub get_name(index: i32) -> *const u8 }, FunctionKind::Virtual);
+
+        let expected = quote! {
+            pub fn get_name(&self, index: i32) -> *const u8 {
+                type Function = extern "fastcall" fn(usize, usize, i32) -> *const u8;
+                let function = unsafe { core::mem::transmute::<usize, Function>((*self.vtable)[(36) as usize]) };
+                function(self as *const _ as usize, 0, index)
+            }
+        };
+
+        assert_eq!(expanded, expected.to_string());
+    }
+
+    #[test]
+    fn expands_a_variadic_function() {
+        let expanded = expand(quote! { 2 con_printf(format: *const u8, ...) }, FunctionKind::Regular);
+
+        let expected = quote! {
+            fn con_printf<V: crate::variadic::VarArgs<(*const u8,), ()>>(&self, format: *const u8, rest: V) -> () {
+                unsafe { rest.call(self.functions[(2) as usize], (format,)) }
+            }
+        };
+
+        assert_eq!(expanded, expected.to_string());
+    }
+
+    #[test]
+    fn expands_a_variadic_entry() {
+        let expanded = expand(
+            quote! {
+                /// Formats into the panel's text.
+                #[cdecl]
+                7 set_text(format: *const u8, ...) -> i32
+            },
+            FunctionKind::Virtual,
+        );
+
+        let expected = quote! {
+            /// Formats into the panel's text.
+            fn set_text<V: crate::variadic::VarArgs<(usize, *const u8,), i32>>(&self, format: *const u8, rest: V) -> i32 {
+                unsafe { rest.call((*self.vtable)[(7) as usize], (self as *const _ as usize, format,)) }
+            }
+        };
+
+        assert_eq!(expanded, expected.to_string());
+    }
+
+    #[test]
+    fn parses_the_header() {
+        let vtable: Vtable = syn::parse2(quote! {
+            size = NUM_VTABLE_ENTRIES;
+            convention = stdcall;
+            entries = PAINT_ENTRIES;
+
+            0 paint(),
+        })
+        .unwrap();
+
+        assert_eq!(vtable.size.to_token_stream().to_string(), "NUM_VTABLE_ENTRIES");
+        assert_eq!(vtable.entries_name.to_token_stream().to_string(), "PAINT_ENTRIES");
+        assert_eq!(vtable.entries.len(), 1);
+    }
+}
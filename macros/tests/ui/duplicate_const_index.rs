@@ -0,0 +1,20 @@
+use sven_coop_hook_macros::vtable;
+
+const GET_NAME: usize = 1;
+const GET_CLASS_NAME: usize = 1;
+
+struct Panel {
+    vtable: *mut [usize; 4],
+}
+
+impl Panel {
+    vtable! {
+        convention = cdecl;
+
+        GET_NAME get_name() -> *const u8,
+        GET_CLASS_NAME get_class_name() -> *const u8,
+        0 get_id() -> usize,
+    }
+}
+
+fn main() {}
@@ -0,0 +1,16 @@
+use sven_coop_hook_macros::vtable;
+
+struct Panel {
+    vtable: *mut [usize; 4],
+}
+
+impl Panel {
+    vtable! {
+        convention = cdecl;
+
+        0 get_name() -> *const u8,
+        0 get_class_name() -> *const u8,
+    }
+}
+
+fn main() {}
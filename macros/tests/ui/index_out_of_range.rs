@@ -0,0 +1,18 @@
+use sven_coop_hook_macros::vtable;
+
+const NUM_VTABLE_ENTRIES: usize = 4;
+
+struct Panel {
+    vtable: *mut [usize; NUM_VTABLE_ENTRIES],
+}
+
+impl Panel {
+    vtable! {
+        size = NUM_VTABLE_ENTRIES;
+        convention = cdecl;
+
+        4 get_name() -> *const u8,
+    }
+}
+
+fn main() {}
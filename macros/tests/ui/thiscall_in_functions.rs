@@ -0,0 +1,14 @@
+use sven_coop_hook_macros::functions;
+
+struct Functions {
+    functions: [usize; 1],
+}
+
+impl Functions {
+    functions! {
+        #[thiscall]
+        0 get_name() -> *const u8,
+    }
+}
+
+fn main() {}
@@ -0,0 +1,15 @@
+use sven_coop_hook_macros::vtable;
+
+struct Panel {
+    vtable: *mut [usize; 4],
+}
+
+impl Panel {
+    vtable! {
+        convention = stdcall;
+
+        0 print(format: *const u8, ...),
+    }
+}
+
+fn main() {}
@@ -0,0 +1,13 @@
+use sven_coop_hook_macros::functions;
+
+struct Functions {
+    functions: [usize; 1],
+}
+
+impl Functions {
+    functions! {
+        0 print(...),
+    }
+}
+
+fn main() {}
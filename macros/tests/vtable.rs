@@ -0,0 +1,103 @@
+use std::ffi::CStr;
+use std::os::raw::c_char;
+
+use sven_coop_hook_macros::{functions, vtable};
+
+// The generated code calls variadic functions through `crate::variadic`.
+#[path = "../../src/variadic.rs"]
+#[allow(clippy::missing_safety_doc)]
+mod variadic;
+
+const NUM_VTABLE_ENTRIES: usize = 4;
+const GET_SIZE: usize = 2;
+
+extern "C" fn get_id(this: usize) -> usize {
+    unsafe { (*(this as *const Panel)).id }
+}
+
+extern "C" fn add(_this: usize, a: i32, b: i32) -> i32 {
+    a + b
+}
+
+extern "C" fn get_size(_this: usize) -> u32 {
+    64
+}
+
+extern "C" fn twice(a: i32) -> i32 {
+    a * 2
+}
+
+struct Panel {
+    vtable: *mut [usize; NUM_VTABLE_ENTRIES],
+    id: usize,
+}
+
+impl Panel {
+    vtable! {
+        size = NUM_VTABLE_ENTRIES;
+        convention = cdecl;
+
+        /// The id that the game gave this panel.
+        0 get_id() -> usize,
+
+        #[cdecl]
+        /// Adds two numbers.
+        1 add(a: i32, b: i32) -> i32,
+    }
+
+    // A second block in the same impl needs its own name for the list of entries.
+    vtable! {
+        size = NUM_VTABLE_ENTRIES;
+        convention = cdecl;
+        entries = SIZE_ENTRIES;
+
+        GET_SIZE get_size() -> u32,
+    }
+}
+
+struct Functions {
+    functions: [usize; 2],
+}
+
+impl Functions {
+    functions! {
+        /// Doubles a number.
+        0 twice(a: i32) -> i32,
+
+        1 snprintf(buffer: *mut c_char, len: usize, format: *const c_char, ...) -> i32,
+    }
+}
+
+#[test]
+fn calls_through_the_vtable() {
+    let mut vtable = [get_id as *const () as usize, add as *const () as usize, get_size as *const () as usize, 0];
+    let panel = Panel { vtable: &mut vtable, id: 7 };
+
+    assert_eq!(panel.get_id(), 7);
+    assert_eq!(panel.add(2, 3), 5);
+    assert_eq!(panel.get_size(), 64);
+}
+
+#[test]
+fn lists_each_block_separately() {
+    assert_eq!(Panel::VTABLE_ENTRIES, [(0, "get_id"), (1, "add")]);
+    assert_eq!(Panel::SIZE_ENTRIES, [(2, "get_size")]);
+}
+
+#[test]
+fn calls_functions() {
+    let functions = Functions { functions: [twice as *const () as usize, libc::snprintf as *const () as usize] };
+    assert_eq!(functions.twice(21), 42);
+
+    let mut buffer = [0 as c_char; 32];
+    let format = b"%s has %d players\0".as_ptr().cast();
+    let written = functions.snprintf(buffer.as_mut_ptr(), buffer.len(), format, (b"svencoop1\0".as_ptr(), 12));
+
+    assert_eq!(written, 24);
+    assert_eq!(unsafe { CStr::from_ptr(buffer.as_ptr()) }.to_str().unwrap(), "svencoop1 has 12 players");
+}
+
+#[test]
+fn ui() {
+    trybuild::TestCases::new().compile_fail("tests/ui/*.rs");
+}
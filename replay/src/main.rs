@@ -0,0 +1,123 @@
+// Replays a capture of user messages through the typed decoders and the features, so that we can check them without
+// the game. Only the features that build off Windows run here, and none of the builtin ones do yet.
+//
+//      cargo run --manifest-path replay/Cargo.toml --target x86_64-unknown-linux-gnu -- session.schc
+//
+// The target is needed because .cargo/config builds for the game by default.
+//
+// Exits with 1 if a record fails to read or to decode.
+
+#![warn(clippy::pedantic)]
+
+// The replay only drives the user message callbacks of the features.
+#[allow(dead_code)]
+#[path = "../../src/feature/mod.rs"]
+mod feature;
+
+#[path = "../../src/message/mod.rs"]
+mod message;
+
+use feature::Features;
+use message::{CaptureReader, Message};
+
+use bstr::ByteSlice;
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::File;
+use std::io::BufReader;
+use std::process;
+
+#[derive(Default)]
+struct Summary {
+    records: usize,
+    raw: usize,
+    failures: usize,
+
+    // How many times each message came in.
+    counts: BTreeMap<String, usize>,
+}
+
+fn main() {
+    let paths: Vec<String> = env::args().skip(1).collect();
+
+    if paths.is_empty() {
+        eprintln!("usage: sven_coop_hook_replay <capture>...");
+        process::exit(2);
+    }
+
+    let features = Features::attach();
+    let mut summary = Summary::default();
+
+    for path in &paths {
+        if let Err(e) = replay(path, &mut summary) {
+            eprintln!("{path}: {e}");
+            summary.failures += 1;
+        }
+    }
+
+    for (name, count) in &summary.counts {
+        println!("{name:<20} {count}");
+    }
+
+    drop(features);
+
+    let Summary { records, raw, failures, .. } = summary;
+    println!("{records} records, {raw} without a decoder, {failures} failures.");
+
+    if failures > 0 {
+        process::exit(1);
+    }
+}
+
+fn replay(path: &str, summary: &mut Summary) -> Result<(), message::capture::Error> {
+    let file = File::open(path)?;
+
+    for record in CaptureReader::new(BufReader::new(file))? {
+        let record = record?;
+
+        summary.records += 1;
+        *summary.counts.entry(record.name.to_string()).or_default() += 1;
+
+        let (time, id) = (record.time, record.id);
+
+        match message::decode(&record.name, &record.bytes) {
+            Ok(Message::Raw(raw)) => {
+                summary.raw += 1;
+                let (name, bytes) = (raw.name, raw.bytes);
+                println!("{time:>10.3} {id:>3} {name:?} {bytes:02x?}");
+            }
+
+            Ok(message) => println!("{time:>10.3} {id:>3} {message:?}"),
+
+            Err(e) => {
+                summary.failures += 1;
+                eprintln!("{path}: {time:.3}: {e}");
+            }
+        }
+
+        // Like the hook, the features see every message, decoded or not.
+        feature::user_msg(record.name.as_bstr(), &record.bytes);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{replay, Summary};
+
+    // We wrote this capture from the decoders' provisional payloads; it doesn't come from the game.
+    const SYNTHETIC: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../tests/fixtures/synthetic.schc");
+
+    #[test]
+    fn replays_the_fixture_without_errors() {
+        let mut summary = Summary::default();
+        replay(SYNTHETIC, &mut summary).unwrap();
+
+        assert_eq!(summary.records, 8);
+        assert_eq!(summary.raw, 1);
+        assert_eq!(summary.failures, 0);
+        assert_eq!(summary.counts["WeaponList"], 1);
+    }
+}
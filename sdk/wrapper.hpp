@@ -8,6 +8,14 @@ typedef struct vec3_s {
     float x, y, z;
 } vec3_t;
 
+// The engine's console command list, from its cmd.h. cdll_int.h only declares it.
+typedef struct cmd_s {
+    struct cmd_s *next;
+    char *name;
+    void (*function)(void);
+    int flags;
+} cmd_t;
+
 #include "common\wrect.h"
 #include "common\cl_dll.h"
 #include "engine\cdll_int.h"
@@ -0,0 +1,163 @@
+// The commands of the console shell, and how we parse a line of the console or the config file into one.
+//
+//      > enable bunny_hop
+//      > log debug
+//      > unload
+
+use log::LevelFilter;
+use thiserror::Error;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command<'a> {
+    Help,
+    Hooks,
+    Features,
+    Enable(&'a str),
+    Disable(&'a str),
+    Entities,
+    UserMsgs,
+    Capture(&'a str),
+    StopCapture,
+    Log(LevelFilter),
+    Reload,
+    Unload,
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum Error<'a> {
+    #[error("unknown command \"{0}\"; try \"help\"")]
+    Unknown(&'a str),
+
+    #[error("\"{command}\" needs a {argument}")]
+    MissingArgument {
+        command: &'a str,
+        argument: &'static str,
+    },
+
+    #[error("\"{command}\" does not take \"{extra}\"")]
+    ExtraArgument {
+        command: &'a str,
+        extra: &'a str,
+    },
+
+    #[error("\"{0}\" is not one of off, error, warn, info, debug, or trace")]
+    Level(&'a str),
+}
+
+// The usage and description of each command, for `help`.
+pub const COMMANDS: &[(&str, &str)] = &[
+    ("help", "Show this text."),
+    ("hooks", "List the hooks, whether they are enabled, and how many times they panicked."),
+    ("features", "List the features and whether they are enabled."),
+    ("enable <feature>", "Enable a feature."),
+    ("disable <feature>", "Disable a feature."),
+    ("entities", "List the entities that the entities feature tracks."),
+    ("user_msgs", "List the user messages that the game registered."),
+    ("capture <path>", "Record every user message to a capture file."),
+    ("capture stop", "Stop recording user messages."),
+    ("log <off|error|warn|info|debug|trace>", "Change the log level."),
+    ("reload", "Run the commands in the config file again."),
+    ("unload", "Unhook and unload."),
+];
+
+// Parses one line. Returns None for a blank line or a `#` comment.
+pub fn parse(line: &str) -> Result<Option<Command<'_>>, Error<'_>> {
+    let line = line.trim();
+
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+
+    let mut words = line.split_whitespace();
+    let name = words.next().unwrap_or_default();
+
+    let mut argument = |argument| words.next().ok_or(Error::MissingArgument { command: name, argument });
+
+    let command = match name {
+        "help" => Command::Help,
+        "hooks" => Command::Hooks,
+        "features" => Command::Features,
+        "enable" => Command::Enable(argument("feature name")?),
+        "disable" => Command::Disable(argument("feature name")?),
+        "entities" => Command::Entities,
+        "user_msgs" => Command::UserMsgs,
+
+        "capture" => match argument("path, or \"stop\"")? {
+            "stop" => Command::StopCapture,
+            path => Command::Capture(path),
+        },
+
+        "log" => Command::Log(parse_level(argument("log level")?)?),
+        "reload" => Command::Reload,
+        "unload" => Command::Unload,
+        _ => return Err(Error::Unknown(name)),
+    };
+
+    if let Some(extra) = words.next() {
+        return Err(Error::ExtraArgument { command: name, extra });
+    }
+
+    Ok(Some(command))
+}
+
+fn parse_level(level: &str) -> Result<LevelFilter, Error<'_>> {
+    Ok(match level {
+        "off" => LevelFilter::Off,
+        "error" => LevelFilter::Error,
+        "warn" => LevelFilter::Warn,
+        "info" => LevelFilter::Info,
+        "debug" => LevelFilter::Debug,
+        "trace" => LevelFilter::Trace,
+        _ => return Err(Error::Level(level)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, Command, Error, COMMANDS};
+    use log::LevelFilter;
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(parse("help"), Ok(Some(Command::Help)));
+        assert_eq!(parse("  enable   bunny_hop  "), Ok(Some(Command::Enable("bunny_hop"))));
+        assert_eq!(parse("disable entities"), Ok(Some(Command::Disable("entities"))));
+        assert_eq!(parse("capture msgs.bin"), Ok(Some(Command::Capture("msgs.bin"))));
+        assert_eq!(parse("capture stop"), Ok(Some(Command::StopCapture)));
+        assert_eq!(parse("log trace"), Ok(Some(Command::Log(LevelFilter::Trace))));
+        assert_eq!(parse("unload"), Ok(Some(Command::Unload)));
+    }
+
+    #[test]
+    fn skips_blank_lines_and_comments() {
+        assert_eq!(parse(""), Ok(None));
+        assert_eq!(parse(" \t "), Ok(None));
+        assert_eq!(parse("# enable bunny_hop"), Ok(None));
+    }
+
+    #[test]
+    fn rejects_bad_lines() {
+        assert_eq!(parse("jump"), Err(Error::Unknown("jump")));
+        assert_eq!(parse("enable"), Err(Error::MissingArgument { command: "enable", argument: "feature name" }));
+        assert_eq!(parse("hooks all"), Err(Error::ExtraArgument { command: "hooks", extra: "all" }));
+        assert_eq!(parse("capture a b"), Err(Error::ExtraArgument { command: "capture", extra: "b" }));
+        assert_eq!(parse("log loud"), Err(Error::Level("loud")));
+    }
+
+    #[test]
+    fn help_shows_commands_that_parse() {
+        for (usage, _) in COMMANDS {
+            let line = usage
+                .split_whitespace()
+                .map(|word| match word {
+                    "<off|error|warn|info|debug|trace>" => "info",
+                    word if word.starts_with('<') => "name",
+                    word => word,
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            assert!(matches!(parse(&line), Ok(Some(_))), "{}", line);
+        }
+    }
+}
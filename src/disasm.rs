@@ -0,0 +1,513 @@
+// A length disassembler for 32-bit x86.
+// It knows where each part of an instruction starts and ends, but not what the instruction means.
+
+use std::fmt;
+
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum Error {
+    #[error("the instruction at {0:#x} runs past the end of the bytes")]
+    Truncated(usize),
+
+    #[error("invalid opcode {opcode} at {address:#x}")]
+    InvalidOpcode {
+        address: usize,
+        opcode: Opcode,
+    },
+
+    #[error("the instruction at {0:#x} is longer than 15 bytes")]
+    TooLong(usize),
+}
+
+pub const MAX_INSTRUCTION_LEN: usize = 15;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Map {
+    OneByte,
+    TwoByte,
+    ThreeByte38,
+    ThreeByte3A,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Opcode {
+    pub map: Map,
+    pub byte: u8,
+    pub vex: bool,
+}
+
+impl fmt::Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.vex {
+            f.write_str("VEX ")?;
+        }
+
+        match self.map {
+            Map::OneByte => write!(f, "{:02X}", self.byte),
+            Map::TwoByte => write!(f, "0F {:02X}", self.byte),
+            Map::ThreeByte38 => write!(f, "0F 38 {:02X}", self.byte),
+            Map::ThreeByte3A => write!(f, "0F 3A {:02X}", self.byte),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ModRm {
+    pub mode: u8,
+    pub reg: u8,
+    pub rm: u8,
+}
+
+impl ModRm {
+    fn from(byte: u8) -> ModRm {
+        ModRm {
+            mode: byte >> 6,
+            reg: (byte >> 3) & 7,
+            rm: byte & 7,
+        }
+    }
+}
+
+// Where one part of an instruction sits, relative to the start of the instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Field {
+    pub offset: usize,
+    pub size: usize,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Prefixes {
+    pub lock: bool,
+    pub rep: Option<u8>,
+    pub segment: Option<u8>,
+    pub operand_size: bool,
+    pub address_size: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Instruction {
+    pub address: usize,
+    pub len: usize,
+    pub bytes: [u8; MAX_INSTRUCTION_LEN],
+    pub prefixes: Prefixes,
+    pub opcode: Opcode,
+    pub modrm: Option<ModRm>,
+    pub sib: Option<u8>,
+    pub displacement: Option<Field>,
+    pub immediate: Option<Field>,
+
+    // The displacement of a relative branch such as `call rel32` or `jne rel8`.
+    pub relative: Option<Field>,
+}
+
+impl Instruction {
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+
+    pub fn next(&self) -> usize {
+        self.address + self.len
+    }
+
+    #[allow(clippy::cast_possible_wrap)]
+    fn signed(&self, field: Field) -> i32 {
+        let bytes = &self.bytes[field.offset..field.offset + field.size];
+
+        match *bytes {
+            [b0] => i32::from(b0 as i8),
+            [b0, b1] => i32::from(i16::from_le_bytes([b0, b1])),
+            [b0, b1, b2, b3] => i32::from_le_bytes([b0, b1, b2, b3]),
+            _ => 0,
+        }
+    }
+
+    // Where a relative branch goes.
+    pub fn target(&self) -> Option<usize> {
+        self.relative.map(|field| {
+            #[allow(clippy::cast_possible_truncation)]
+            let next = self.next() as u32;
+
+            #[allow(clippy::cast_sign_loss)]
+            let displacement = self.signed(field) as u32;
+
+            next.wrapping_add(displacement) as usize
+        })
+    }
+}
+
+// What follows an opcode.
+const M: u16 = 1 << 0; // ModRM
+const I8: u16 = 1 << 1; // imm8
+const IZ: u16 = 1 << 2; // imm16 or imm32, by operand size
+const I16: u16 = 1 << 3; // imm16
+const R8: u16 = 1 << 4; // rel8
+const RZ: u16 = 1 << 5; // rel16 or rel32, by operand size
+const MO: u16 = 1 << 6; // moffs16 or moffs32, by address size
+const FAR: u16 = 1 << 7; // ptr16:16 or ptr16:32, by operand size
+const G3: u16 = 1 << 8; // F6 and F7: /0 and /1 have an immediate
+const X: u16 = 1 << 9; // invalid
+const MI8: u16 = M | I8;
+const MIZ: u16 = M | IZ;
+
+#[rustfmt::skip]
+const ONE_BYTE: [u16; 256] = [
+//  0      1      2      3      4      5      6      7      8      9      A      B      C      D      E      F
+    M,     M,     M,     M,     I8,    IZ,    0,     0,     M,     M,     M,     M,     I8,    IZ,    0,     X,     // 0
+    M,     M,     M,     M,     I8,    IZ,    0,     0,     M,     M,     M,     M,     I8,    IZ,    0,     0,     // 1
+    M,     M,     M,     M,     I8,    IZ,    X,     0,     M,     M,     M,     M,     I8,    IZ,    X,     0,     // 2
+    M,     M,     M,     M,     I8,    IZ,    X,     0,     M,     M,     M,     M,     I8,    IZ,    X,     0,     // 3
+    0,     0,     0,     0,     0,     0,     0,     0,     0,     0,     0,     0,     0,     0,     0,     0,     // 4
+    0,     0,     0,     0,     0,     0,     0,     0,     0,     0,     0,     0,     0,     0,     0,     0,     // 5
+    0,     0,     M,     M,     X,     X,     X,     X,     IZ,    MIZ,   I8,    MI8,   0,     0,     0,     0,     // 6
+    R8,    R8,    R8,    R8,    R8,    R8,    R8,    R8,    R8,    R8,    R8,    R8,    R8,    R8,    R8,    R8,    // 7
+    MI8,   MIZ,   MI8,   MI8,   M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     // 8
+    0,     0,     0,     0,     0,     0,     0,     0,     0,     0,     FAR,   0,     0,     0,     0,     0,     // 9
+    MO,    MO,    MO,    MO,    0,     0,     0,     0,     I8,    IZ,    0,     0,     0,     0,     0,     0,     // A
+    I8,    I8,    I8,    I8,    I8,    I8,    I8,    I8,    IZ,    IZ,    IZ,    IZ,    IZ,    IZ,    IZ,    IZ,    // B
+    MI8,   MI8,   I16,   0,     M,     M,     MI8,   MIZ,   I16|I8,0,     I16,   0,     0,     I8,    0,     0,     // C
+    M,     M,     M,     M,     I8,    I8,    0,     0,     M,     M,     M,     M,     M,     M,     M,     M,     // D
+    R8,    R8,    R8,    R8,    I8,    I8,    I8,    I8,    RZ,    RZ,    FAR,   R8,    0,     0,     0,     0,     // E
+    X,     0,     X,     X,     0,     0,     M|G3,  M|G3,  0,     0,     0,     0,     0,     0,     M,     M,     // F
+];
+
+#[rustfmt::skip]
+const TWO_BYTE: [u16; 256] = [
+//  0      1      2      3      4      5      6      7      8      9      A      B      C      D      E      F
+    M,     M,     M,     M,     X,     0,     0,     0,     0,     0,     X,     0,     X,     M,     0,     MI8,   // 0
+    M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     // 1
+    M,     M,     M,     M,     X,     X,     X,     X,     M,     M,     M,     M,     M,     M,     M,     M,     // 2
+    0,     0,     0,     0,     0,     0,     X,     0,     X,     X,     X,     X,     X,     X,     X,     X,     // 3
+    M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     // 4
+    M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     // 5
+    M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     // 6
+    MI8,   MI8,   MI8,   MI8,   M,     M,     M,     0,     M,     M,     X,     X,     M,     M,     M,     M,     // 7
+    RZ,    RZ,    RZ,    RZ,    RZ,    RZ,    RZ,    RZ,    RZ,    RZ,    RZ,    RZ,    RZ,    RZ,    RZ,    RZ,    // 8
+    M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     // 9
+    0,     0,     0,     M,     MI8,   M,     X,     X,     0,     0,     0,     M,     MI8,   M,     M,     M,     // A
+    M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     MI8,   M,     M,     M,     M,     M,     // B
+    M,     M,     MI8,   M,     MI8,   MI8,   MI8,   M,     0,     0,     0,     0,     0,     0,     0,     0,     // C
+    M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     // D
+    M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     // E
+    M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     M,     // F
+];
+
+// Every opcode in the 0F 38 map has a ModRM and no immediate.
+// Every opcode in the 0F 3A map has a ModRM and an imm8.
+const THREE_BYTE_38: u16 = M;
+const THREE_BYTE_3A: u16 = MI8;
+
+struct Reader<'b> {
+    bytes: &'b [u8],
+    address: usize,
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn peek(&self) -> Result<u8, Error> {
+        if self.position >= MAX_INSTRUCTION_LEN {
+            return Err(Error::TooLong(self.address));
+        }
+
+        self.bytes
+            .get(self.position)
+            .copied()
+            .ok_or(Error::Truncated(self.address))
+    }
+
+    fn byte(&mut self) -> Result<u8, Error> {
+        let byte = self.peek()?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    fn field(&mut self, size: usize) -> Result<Option<Field>, Error> {
+        if size == 0 {
+            return Ok(None);
+        }
+
+        let field = Field { offset: self.position, size };
+        self.position += size;
+
+        if self.position > MAX_INSTRUCTION_LEN {
+            Err(Error::TooLong(self.address))
+        } else if self.position > self.bytes.len() {
+            Err(Error::Truncated(self.address))
+        } else {
+            Ok(Some(field))
+        }
+    }
+}
+
+pub fn decode(bytes: &[u8], address: usize) -> Result<Instruction, Error> {
+    let mut reader = Reader { bytes, address, position: 0 };
+    let mut prefixes = Prefixes::default();
+
+    let mut byte = loop {
+        match reader.byte()? {
+            0xF0 => prefixes.lock = true,
+            rep @ (0xF2 | 0xF3) => prefixes.rep = Some(rep),
+            segment @ (0x26 | 0x2E | 0x36 | 0x3E | 0x64 | 0x65) => prefixes.segment = Some(segment),
+            0x66 => prefixes.operand_size = true,
+            0x67 => prefixes.address_size = true,
+            byte => break byte,
+        }
+    };
+
+    let mut vex = false;
+
+    // In 32-bit mode, C4 and C5 are LES and LDS unless the next byte would be a register ModRM.
+    if (byte == 0xC4 || byte == 0xC5) && reader.peek()? >= 0xC0 {
+        vex = true;
+
+        let map = if byte == 0xC5 {
+            reader.byte()?;
+            Map::TwoByte
+        } else {
+            let map = reader.byte()? & 0x1F;
+            reader.byte()?;
+
+            match map {
+                1 => Map::TwoByte,
+                2 => Map::ThreeByte38,
+                3 => Map::ThreeByte3A,
+                _ => return Err(Error::InvalidOpcode { address, opcode: Opcode { map: Map::OneByte, byte, vex } }),
+            }
+        };
+
+        byte = reader.byte()?;
+        return finish(reader, prefixes, Opcode { map, byte, vex });
+    }
+
+    let map = if byte == 0x0F {
+        byte = reader.byte()?;
+
+        match byte {
+            0x38 => {
+                byte = reader.byte()?;
+                Map::ThreeByte38
+            }
+
+            0x3A => {
+                byte = reader.byte()?;
+                Map::ThreeByte3A
+            }
+
+            _ => Map::TwoByte,
+        }
+    } else {
+        Map::OneByte
+    };
+
+    finish(reader, prefixes, Opcode { map, byte, vex })
+}
+
+fn finish(mut reader: Reader, prefixes: Prefixes, opcode: Opcode) -> Result<Instruction, Error> {
+    let flags = match opcode.map {
+        Map::OneByte => ONE_BYTE[usize::from(opcode.byte)],
+        Map::TwoByte => TWO_BYTE[usize::from(opcode.byte)],
+        Map::ThreeByte38 => THREE_BYTE_38,
+        Map::ThreeByte3A => THREE_BYTE_3A,
+    };
+
+    if flags & X != 0 {
+        return Err(Error::InvalidOpcode { address: reader.address, opcode });
+    }
+
+    let operand_size = if prefixes.operand_size { 2 } else { 4 };
+    let address_size = if prefixes.address_size { 2 } else { 4 };
+
+    let mut modrm = None;
+    let mut sib = None;
+    let mut displacement = None;
+
+    if flags & M != 0 {
+        let m = ModRm::from(reader.byte()?);
+        modrm = Some(m);
+
+        let displacement_size = if m.mode == 3 {
+            0
+        } else if address_size == 2 {
+            match (m.mode, m.rm) {
+                (0, 6) | (2, _) => 2,
+                (1, _) => 1,
+                _ => 0,
+            }
+        } else {
+            let mut base = m.rm;
+
+            if m.rm == 4 {
+                let s = reader.byte()?;
+                sib = Some(s);
+                base = s & 7;
+            }
+
+            match (m.mode, base) {
+                (0, 5) | (2, _) => 4,
+                (1, _) => 1,
+                _ => 0,
+            }
+        };
+
+        displacement = reader.field(displacement_size)?;
+    }
+
+    let mut immediate_size = 0;
+
+    if flags & I16 != 0 {
+        immediate_size += 2;
+    }
+
+    if flags & I8 != 0 {
+        immediate_size += 1;
+    }
+
+    if flags & IZ != 0 {
+        immediate_size += operand_size;
+    }
+
+    if flags & G3 != 0 && modrm.is_some_and(|m| m.reg <= 1) {
+        immediate_size += if opcode.byte == 0xF6 { 1 } else { operand_size };
+    }
+
+    if flags & MO != 0 {
+        displacement = reader.field(address_size)?;
+    }
+
+    if flags & FAR != 0 {
+        immediate_size += operand_size + 2;
+    }
+
+    let immediate = reader.field(immediate_size)?;
+
+    let relative = if flags & R8 != 0 {
+        reader.field(1)?
+    } else if flags & RZ != 0 {
+        reader.field(operand_size)?
+    } else {
+        None
+    };
+
+    let len = reader.position;
+    let mut bytes = [0; MAX_INSTRUCTION_LEN];
+    bytes[..len].copy_from_slice(&reader.bytes[..len]);
+
+    Ok(Instruction {
+        address: reader.address,
+        len,
+        bytes,
+        prefixes,
+        opcode,
+        modrm,
+        sib,
+        displacement,
+        immediate,
+        relative,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, Error, Field, Map, Opcode};
+
+    // bytes, length, displacement, immediate, relative
+    type Case = (&'static [u8], usize, Option<Field>, Option<Field>, Option<Field>);
+
+    #[allow(clippy::unnecessary_wraps)]
+    const fn field(offset: usize, size: usize) -> Option<Field> {
+        Some(Field { offset, size })
+    }
+
+    #[test]
+    fn lengths_and_fields() {
+        #[rustfmt::skip]
+        let cases: &[Case] = &[
+            (&[0x90],                                     1,  None,         None,         None),         // nop
+            (&[0x55],                                     1,  None,         None,         None),         // push ebp
+            (&[0xC3],                                     1,  None,         None,         None),         // ret
+            (&[0xC2, 0x08, 0x00],                         3,  None,         field(1, 2),  None),         // ret 8
+            (&[0x6A, 0x07],                               2,  None,         field(1, 1),  None),         // push 7
+            (&[0x68, 0x78, 0x56, 0x34, 0x12],             5,  None,         field(1, 4),  None),         // push imm32
+            (&[0x66, 0x68, 0x34, 0x12],                   4,  None,         field(2, 2),  None),         // push imm16
+            (&[0xB8, 0x01, 0x00, 0x00, 0x00],             5,  None,         field(1, 4),  None),         // mov eax,1
+            (&[0x8B, 0xEC],                               2,  None,         None,         None),         // mov ebp,esp
+            (&[0x8B, 0x45, 0x08],                         3,  field(2, 1),  None,         None),         // mov eax,[ebp+8]
+            (&[0x8B, 0x85, 0x00, 0x01, 0x00, 0x00],       6,  field(2, 4),  None,         None),         // mov eax,[ebp+100]
+            (&[0x8B, 0x35, 0x50, 0x8D, 0xB5, 0x04],       6,  field(2, 4),  None,         None),         // mov esi,[imm32]
+            (&[0x8B, 0x5C, 0x24, 0x08],                   4,  field(3, 1),  None,         None),         // mov ebx,[esp+8]
+            (&[0x8B, 0x04, 0x24],                         3,  None,         None,         None),         // mov eax,[esp]
+            (&[0x8B, 0x04, 0x85, 0, 0, 0, 0],             7,  field(3, 4),  None,         None),         // mov eax,[eax*4+imm32]
+            (&[0x67, 0x8B, 0x46, 0x02],                   4,  field(3, 1),  None,         None),         // mov eax,[bp+2]
+            (&[0x67, 0x8B, 0x06, 0x34, 0x12],             5,  field(3, 2),  None,         None),         // mov eax,[imm16]
+            (&[0xFF, 0x15, 0x00, 0x03, 0x00, 0x01],       6,  field(2, 4),  None,         None),         // call [imm32]
+            (&[0xFF, 0x74, 0x24, 0x08],                   4,  field(3, 1),  None,         None),         // push [esp+8]
+            (&[0x83, 0xC4, 0x0C],                         3,  None,         field(2, 1),  None),         // add esp,C
+            (&[0x81, 0xEC, 0x00, 0x01, 0x00, 0x00],       6,  None,         field(2, 4),  None),         // sub esp,100
+            (&[0xC7, 0x45, 0xFC, 1, 0, 0, 0],             7,  field(2, 1),  field(3, 4),  None),         // mov [ebp-4],1
+            (&[0x66, 0xC7, 0x45, 0xFC, 1, 0],             6,  field(3, 1),  field(4, 2),  None),         // mov word [ebp-4],1
+            (&[0xF6, 0xC1, 0x01],                         3,  None,         field(2, 1),  None),         // test cl,1
+            (&[0xF6, 0xD9],                               2,  None,         None,         None),         // neg cl
+            (&[0xF7, 0xC1, 1, 0, 0, 0],                   6,  None,         field(2, 4),  None),         // test ecx,1
+            (&[0xF7, 0xE1],                               2,  None,         None,         None),         // mul ecx
+            (&[0xA1, 0x00, 0x02, 0x00, 0x01],             5,  field(1, 4),  None,         None),         // mov eax,[moffs32]
+            (&[0x67, 0xA1, 0x34, 0x12],                   4,  field(2, 2),  None,         None),         // mov eax,[moffs16]
+            (&[0xC8, 0x10, 0x00, 0x00],                   4,  None,         field(1, 3),  None),         // enter 10,0
+            (&[0x9A, 1, 2, 3, 4, 5, 6],                   7,  None,         field(1, 6),  None),         // call far
+            (&[0x75, 0x05],                               2,  None,         None,         field(1, 1)),  // jne rel8
+            (&[0xE8, 0x10, 0x00, 0x00, 0x00],             5,  None,         None,         field(1, 4)),  // call rel32
+            (&[0xE9, 0x10, 0x00, 0x00, 0x00],             5,  None,         None,         field(1, 4)),  // jmp rel32
+            (&[0x66, 0xE9, 0x10, 0x00],                   4,  None,         None,         field(2, 2)),  // jmp rel16
+            (&[0x0F, 0x84, 0x10, 0x00, 0x00, 0x00],       6,  None,         None,         field(2, 4)),  // je rel32
+            (&[0x0F, 0xB6, 0x45, 0x08],                   4,  field(3, 1),  None,         None),         // movzx eax,byte [ebp+8]
+            (&[0x0F, 0xBA, 0xE0, 0x03],                   4,  None,         field(3, 1),  None),         // bt eax,3
+            (&[0x0F, 0x38, 0x00, 0xC1],                   4,  None,         None,         None),         // pshufb mm0,mm1
+            (&[0x0F, 0x3A, 0x0F, 0xC1, 0x08],             5,  None,         field(4, 1),  None),         // palignr mm0,mm1,8
+            (&[0xF3, 0x0F, 0x10, 0x45, 0x08],             5,  field(4, 1),  None,         None),         // movss xmm0,[ebp+8]
+            (&[0xF0, 0x0F, 0xB1, 0x0A],                   4,  None,         None,         None),         // lock cmpxchg [edx],ecx
+            (&[0x64, 0xA1, 0x00, 0x00, 0x00, 0x00],       6,  field(2, 4),  None,         None),         // mov eax,fs:[0]
+            (&[0xC5, 0xF8, 0x77],                         3,  None,         None,         None),         // vzeroupper
+            (&[0xC4, 0xE3, 0x79, 0x0F, 0xC1, 0x08],       6,  None,         field(5, 1),  None),         // vpalignr
+            (&[0xC4, 0x45, 0x08],                         3,  field(2, 1),  None,         None),         // les eax,[ebp+8]
+            (&[0xD9, 0x45, 0x08],                         3,  field(2, 1),  None,         None),         // fld dword [ebp+8]
+        ];
+
+        for &(bytes, len, displacement, immediate, relative) in cases {
+            let instruction = decode(bytes, 0x1000).unwrap_or_else(|e| panic!("{:02X?}: {}", bytes, e));
+
+            assert_eq!(
+                (instruction.len, instruction.displacement, instruction.immediate, instruction.relative),
+                (len, displacement, immediate, relative),
+                "{bytes:02X?}"
+            );
+
+            assert_eq!(instruction.bytes(), bytes);
+        }
+    }
+
+    #[test]
+    fn branch_targets() {
+        assert_eq!(decode(&[0x75, 0x05], 0x1000).unwrap().target(), Some(0x1007));
+        assert_eq!(decode(&[0xEB, 0xFE], 0x1000).unwrap().target(), Some(0x1000));
+        assert_eq!(decode(&[0xE8, 0xFB, 0xFF, 0xFF, 0xFF], 0x1000).unwrap().target(), Some(0x1000));
+        assert_eq!(decode(&[0xE8, 0x83, 0x25, 0x01, 0x00], 0x420_A278).unwrap().target(), Some(0x421_C800));
+        assert_eq!(decode(&[0x68, 0, 0, 0, 0], 0x1000).unwrap().target(), None);
+    }
+
+    #[test]
+    fn bad_instructions() {
+        let invalid = |map, byte| Err(Error::InvalidOpcode { address: 0x1000, opcode: Opcode { map, byte, vex: false } });
+
+        assert_eq!(decode(&[0x0F, 0x04], 0x1000).map(|i| i.len), invalid(Map::TwoByte, 0x04));
+        assert_eq!(decode(&[0xF1], 0x1000).map(|i| i.len), Ok(1));
+        assert_eq!(decode(&[0xD6], 0x1000).map(|i| i.len), Ok(1));
+        assert_eq!(decode(&[0x0F, 0x0A], 0x1000).map(|i| i.len), invalid(Map::TwoByte, 0x0A));
+
+        assert_eq!(decode(&[], 0x1000).map(|i| i.len), Err(Error::Truncated(0x1000)));
+        assert_eq!(decode(&[0x68, 0, 0], 0x1000).map(|i| i.len), Err(Error::Truncated(0x1000)));
+        assert_eq!(decode(&[0x8B], 0x1000).map(|i| i.len), Err(Error::Truncated(0x1000)));
+        assert_eq!(decode(&[0x0F], 0x1000).map(|i| i.len), Err(Error::Truncated(0x1000)));
+        assert_eq!(decode(&[0x66; 16], 0x1000).map(|i| i.len), Err(Error::TooLong(0x1000)));
+        assert_eq!(decode(&[0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0xC7, 0x05, 0, 0, 0, 0, 0, 0], 0x1000)
+                       .map(|i| i.len), Err(Error::TooLong(0x1000)));
+    }
+}
@@ -0,0 +1,227 @@
+// Lets features subscribe to a hooked function instead of editing the hook.
+// A subscriber runs either before the original, where it may change the arguments or suppress the original,
+// or after the original, where it may change the return value.
+// A subscriber that panics is disabled on its own. The rest of the subscribers and the original still run.
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use log::error;
+use once_cell::sync::Lazy;
+
+use crate::guard;
+
+// BEGIN MUTABLE GLOBAL STATE
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+// END MUTABLE GLOBAL STATE
+
+pub enum Action<R> {
+    // Run the rest of the subscribers and then the original.
+    Continue,
+
+    // Skip the rest of the before subscribers and the original, and return this instead.
+    // The after subscribers still run.
+    Suppress(R),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SubscriberId(u64);
+
+type Before<A, R> = dyn Fn(&mut A) -> Action<R> + Send + Sync;
+type After<A, R> = dyn Fn(&mut A, &mut R) + Send + Sync;
+
+enum Callback<A, R> {
+    Before(Arc<Before<A, R>>),
+    After(Arc<After<A, R>>),
+}
+
+struct Subscriber<A, R> {
+    id: SubscriberId,
+    priority: i32,
+    callback: Callback<A, R>,
+
+    // Shared by every snapshot, so that disabling the subscriber sticks.
+    enabled: Arc<AtomicBool>,
+}
+
+impl<A, R> Subscriber<A, R> {
+    // Runs the callback unless the subscriber is disabled.
+    // In debug builds, a panic disables the subscriber and returns None. Release builds abort on panic.
+    fn run<T>(&self, callback: impl FnOnce() -> T) -> Option<T> {
+        if !self.enabled.load(Ordering::Relaxed) {
+            return None;
+        }
+
+        if !cfg!(debug_assertions) {
+            return Some(callback());
+        }
+
+        match panic::catch_unwind(AssertUnwindSafe(callback)) {
+            Ok(value) => Some(value),
+            Err(payload) => {
+                self.enabled.store(false, Ordering::Relaxed);
+                error!("Subscriber {:?} panicked with: {}. Disabled it.", self.id, guard::describe(payload.as_ref()));
+                None
+            }
+        }
+    }
+}
+
+// Not derived, because a derive would require `A: Clone` and `R: Clone`.
+impl<A, R> Clone for Subscriber<A, R> {
+    fn clone(&self) -> Subscriber<A, R> {
+        Subscriber {
+            id: self.id,
+            priority: self.priority,
+            callback: match &self.callback {
+                Callback::Before(callback) => Callback::Before(Arc::clone(callback)),
+                Callback::After(callback) => Callback::After(Arc::clone(callback)),
+            },
+            enabled: Arc::clone(&self.enabled),
+        }
+    }
+}
+
+type Snapshot<A, R> = Arc<Vec<Subscriber<A, R>>>;
+
+// The subscribers of one hooked function, highest priority first.
+// A dispatch works on a snapshot, so a subscriber may subscribe or unsubscribe without deadlocking.
+pub struct Subscribers<A, R> {
+    list: Lazy<Mutex<Snapshot<A, R>>>,
+}
+
+impl<A, R> Subscribers<A, R> {
+    pub const fn new() -> Subscribers<A, R> {
+        Subscribers {
+            list: Lazy::new(Mutex::default),
+        }
+    }
+
+    pub fn before(&self, priority: i32, callback: impl Fn(&mut A) -> Action<R> + Send + Sync + 'static) -> SubscriberId {
+        self.add(priority, Callback::Before(Arc::new(callback)))
+    }
+
+    pub fn after(&self, priority: i32, callback: impl Fn(&mut A, &mut R) + Send + Sync + 'static) -> SubscriberId {
+        self.add(priority, Callback::After(Arc::new(callback)))
+    }
+
+    // Returns whether `id` was subscribed.
+    pub fn remove(&self, id: SubscriberId) -> bool {
+        let mut removed = false;
+
+        self.update(|list| {
+            let len = list.len();
+            list.retain(|subscriber| subscriber.id != id);
+            removed = list.len() != len;
+        });
+
+        removed
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshot().len()
+    }
+
+    pub fn run_before(&self, args: &mut A) -> Action<R> {
+        for subscriber in self.snapshot().iter() {
+            if let Callback::Before(callback) = &subscriber.callback {
+                if let Some(Action::Suppress(value)) = subscriber.run(|| callback(args)) {
+                    return Action::Suppress(value);
+                }
+            }
+        }
+
+        Action::Continue
+    }
+
+    pub fn run_after(&self, args: &mut A, result: &mut R) {
+        for subscriber in self.snapshot().iter() {
+            if let Callback::After(callback) = &subscriber.callback {
+                subscriber.run(|| callback(args, result));
+            }
+        }
+    }
+
+    fn add(&self, priority: i32, callback: Callback<A, R>) -> SubscriberId {
+        let id = SubscriberId(NEXT_ID.fetch_add(1, Ordering::Relaxed));
+
+        self.update(|list| {
+            // Subscribers of equal priority run in the order they subscribed.
+            let position = list.iter().position(|subscriber| subscriber.priority < priority).unwrap_or(list.len());
+            list.insert(position, Subscriber { id, priority, callback, enabled: Arc::new(AtomicBool::new(true)) });
+        });
+
+        id
+    }
+
+    fn snapshot(&self) -> Snapshot<A, R> {
+        match self.list.lock() {
+            Ok(list) => Arc::clone(&list),
+            Err(poisoned) => Arc::clone(&poisoned.into_inner()),
+        }
+    }
+
+    // Copies the list, changes the copy, and swaps it in.
+    fn update(&self, change: impl FnOnce(&mut Vec<Subscriber<A, R>>)) {
+        let mut guard = match self.list.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        let mut list = Vec::clone(&guard);
+        change(&mut list);
+        *guard = Arc::new(list);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Action, Subscribers};
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    static PANICS: AtomicU32 = AtomicU32::new(0);
+
+    #[test]
+    fn a_panicking_subscriber_is_disabled_alone() {
+        let subscribers = Subscribers::<i32, i32>::new();
+        subscribers.before(1, |_| { PANICS.fetch_add(1, Ordering::Relaxed); panic!("before") });
+        subscribers.before(0, |args| { *args += 1; Action::Continue });
+        subscribers.after(1, |_, _| { PANICS.fetch_add(1, Ordering::Relaxed); panic!("after") });
+        subscribers.after(0, |args, result| *result += *args);
+
+        for _ in 0..2 {
+            let mut args = 3;
+            let mut result = 1;
+
+            assert!(matches!(subscribers.run_before(&mut args), Action::Continue));
+            subscribers.run_after(&mut args, &mut result);
+            assert_eq!(result, 5);
+        }
+
+        assert_eq!(PANICS.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn removed_subscribers_stop_running() {
+        let subscribers = Subscribers::<i32, i32>::new();
+        let suppress = subscribers.before(0, |_| Action::Suppress(7));
+        let double = subscribers.after(0, |_, result| *result *= 2);
+        assert_eq!(subscribers.len(), 2);
+
+        let mut args = 0;
+        let mut result = 1;
+        assert!(matches!(subscribers.run_before(&mut args), Action::Suppress(7)));
+        subscribers.run_after(&mut args, &mut result);
+        assert_eq!(result, 2);
+
+        assert!(subscribers.remove(suppress));
+        assert!(subscribers.remove(double));
+        assert!(!subscribers.remove(double));
+        assert_eq!(subscribers.len(), 0);
+
+        assert!(matches!(subscribers.run_before(&mut args), Action::Continue));
+        subscribers.run_after(&mut args, &mut result);
+        assert_eq!(result, 2);
+    }
+}
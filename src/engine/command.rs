@@ -0,0 +1,173 @@
+// `sch_*` console commands that run Rust closures.
+// The engine calls a command without arguments, so every command points at `dispatch`,
+// which looks up the closure by Cmd_Argv(0). The engine actually calls a stub that we never free:
+//
+//      jmp dispatch
+//      ret
+//
+// The engine can't remove a command, so when we unload, we point the jmp at the ret, and our commands do nothing.
+// When we load again, we point the commands that the engine still has at the new stub.
+
+use crate::game::cmd_s;
+use crate::guard::{self, HookState};
+use crate::inline_hook::{jmp, JMP_LEN};
+use crate::memory::ExecutableMemory;
+use crate::yank::Yank;
+
+use super::{c_name, Error};
+
+use std::ffi::{CStr, CString};
+use std::iter;
+use std::mem::{self, ManuallyDrop};
+use std::ptr;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use log::{info, warn};
+use once_cell::sync::Lazy;
+use rustc_hash::FxHashMap;
+
+const RET: u8 = 0xC3;
+const STUB_LEN: usize = JMP_LEN + 1;
+
+// BEGIN MUTABLE GLOBAL STATE
+use crate::hook::ENGINE_FUNCS;
+static COMMANDS: Lazy<Mutex<Commands>> = Lazy::new(Mutex::default);
+static mut STUB: usize = 0;
+// END MUTABLE GLOBAL STATE
+
+type Callback = dyn Fn(&[String]) + Send + Sync;
+
+struct Command {
+    callback: Arc<Callback>,
+    state: &'static HookState,
+}
+
+#[derive(Default)]
+struct Commands {
+    // Lowercase, because the engine ignores case when it matches a command.
+    registered: FxHashMap<String, Command>,
+    queued: Vec<CString>,
+}
+
+fn lock() -> MutexGuard<'static, Commands> {
+    COMMANDS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// Runs `callback` with the arguments after the name whenever someone enters `name` in the game's console.
+//
+//      engine::register("sch_hello", |args| engine::print(&format!("Hello, {}!\n", args.join(" "))))?;
+pub fn register(name: &str, callback: impl Fn(&[String]) + Send + Sync + 'static) -> Result<(), Error> {
+    let c_name = c_name(name)?;
+    let mut commands = lock();
+    let key = name.to_ascii_lowercase();
+
+    if commands.registered.contains_key(&key) {
+        return Err(Error::Duplicate(String::from(name)));
+    }
+
+    // Commands show up in the console's `hooks` list for as long as we're loaded.
+    let state = Box::leak(Box::new(HookState::new(Box::leak(name.into()))));
+
+    commands.registered.insert(key, Command { callback: Arc::new(callback), state });
+    commands.queued.push(c_name);
+    Ok(())
+}
+
+pub struct Stub {
+    memory: ManuallyDrop<ExecutableMemory>,
+}
+
+impl Stub {
+    pub fn new() -> Result<Stub, Error> {
+        let mut memory = ExecutableMemory::new(STUB_LEN)?;
+        let address = memory.address();
+
+        let code = memory.as_mut_slice();
+        code[..JMP_LEN].copy_from_slice(&jmp(address, dispatch as usize));
+        code[JMP_LEN] = RET;
+
+        unsafe {
+            STUB = address;
+        }
+
+        Ok(Stub {
+            memory: ManuallyDrop::new(memory),
+        })
+    }
+}
+
+impl Drop for Stub {
+    fn drop(&mut self) {
+        let address = self.memory.address();
+        let [_, rel32 @ ..] = jmp(address, address + JMP_LEN);
+
+        // One unaligned 4-byte store, which x86 does atomically within a cache line,
+        // in case the game thread runs a command right now.
+        unsafe {
+            STUB = 0;
+            ((address + 1) as *mut [u8; 4]).write_unaligned(rel32);
+        }
+
+        info!("Disarmed our console commands.");
+    }
+}
+
+unsafe extern "C" fn dispatch() {
+    crate::single_thread_verifier::assert();
+
+    let funcs = &*ENGINE_FUNCS;
+    let count = funcs.Cmd_Argc.yank()();
+    let arg = funcs.Cmd_Argv.yank();
+
+    let args: Vec<String> = (0..count)
+        .map(|i| arg(i))
+        .filter(|arg| !arg.is_null())
+        .map(|arg| CStr::from_ptr(arg).to_string_lossy().into_owned())
+        .collect();
+
+    let Some(name) = args.first() else {
+        return;
+    };
+
+    // We don't hold the lock during the callback, so that the callback can register commands.
+    let command = lock()
+        .registered
+        .get(&name.to_ascii_lowercase())
+        .map(|command| (Arc::clone(&command.callback), command.state));
+
+    if let Some((callback, state)) = command {
+        guard::run(state, || callback(&args[1..]));
+    } else {
+        warn!("The engine ran {name:?}, which we did not register.");
+    }
+}
+
+// Hands the queued commands to the engine.
+pub unsafe fn add_queued() {
+    if STUB == 0 {
+        return;
+    }
+
+    let queued = mem::take(&mut lock().queued);
+    let funcs = &*ENGINE_FUNCS;
+    let function: unsafe extern "C" fn() = mem::transmute(STUB);
+
+    for name in queued {
+        if let Some(existing) = find(&name) {
+            (*existing).function = Some(function);
+            info!("Took over the command {name:?} that we added in an earlier load.");
+        } else {
+            // The engine keeps the name for good.
+            funcs.pfnAddCommand.yank()(name.clone().into_raw(), Some(function));
+            info!("Added the command {name:?}.");
+        }
+    }
+}
+
+unsafe fn find(name: &CStr) -> Option<*mut cmd_s> {
+    let first = (*ENGINE_FUNCS).pfnGetCmdList.yank()();
+
+    iter::successors(first.as_mut(), |cmd| cmd.next.as_mut())
+        .find(|cmd| !cmd.name.is_null() && CStr::from_ptr(cmd.name).to_bytes().eq_ignore_ascii_case(name.to_bytes()))
+        .map(ptr::from_mut)
+}
@@ -0,0 +1,194 @@
+// `sch_*` cvars with typed values. Any thread can read them.
+// The engine's copy belongs to the game thread, so we copy each value from it once a frame and read the copy.
+//
+//      static IN_WATER: Cvar<bool> = Cvar::new("sch_bunny_hop_in_water", "1");
+//
+//      IN_WATER.register()?;
+//
+//      if IN_WATER.get() {
+//          ...
+//      }
+
+use crate::game::cvar_s;
+use crate::yank::Yank;
+
+use super::{c_name, Error};
+
+use std::ffi::{CStr, CString};
+use std::marker::PhantomData;
+use std::mem;
+use std::ptr;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+
+use log::{error, info};
+use once_cell::sync::Lazy;
+
+// BEGIN MUTABLE GLOBAL STATE
+use crate::hook::ENGINE_FUNCS;
+static CVARS: Lazy<Mutex<Cvars>> = Lazy::new(Mutex::default);
+// END MUTABLE GLOBAL STATE
+
+// A type that a cvar can hold. The engine keeps both a float and a string for every cvar.
+pub trait Value {
+    fn from_cvar(value: f32, string: &str) -> Self;
+}
+
+impl Value for bool {
+    fn from_cvar(value: f32, _: &str) -> bool {
+        value != 0.0
+    }
+}
+
+impl Value for i32 {
+    #[allow(clippy::cast_possible_truncation)]
+    fn from_cvar(value: f32, _: &str) -> i32 {
+        value as i32
+    }
+}
+
+impl Value for f32 {
+    fn from_cvar(value: f32, _: &str) -> f32 {
+        value
+    }
+}
+
+impl Value for String {
+    fn from_cvar(_: f32, string: &str) -> String {
+        String::from(string)
+    }
+}
+
+// The part of a cvar that doesn't depend on its type, so that one queue holds every cvar.
+struct Slot {
+    name: &'static str,
+    default: &'static str,
+    cvar: AtomicPtr<cvar_s>,
+    registered: AtomicBool,
+
+    // What the engine held at the last frame.
+    held: Mutex<Option<Held>>,
+}
+
+struct Held {
+    value: f32,
+    string: String,
+}
+
+impl Slot {
+    fn held(&self) -> MutexGuard<'_, Option<Held>> {
+        self.held.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // Call this on the game thread.
+    unsafe fn refresh(&self) {
+        let cvar = match self.cvar.load(Ordering::Relaxed).as_ref() {
+            Some(cvar) if !cvar.string.is_null() => cvar,
+            _ => return,
+        };
+
+        let string = CStr::from_ptr(cvar.string).to_string_lossy();
+        let mut held = self.held();
+
+        match &mut *held {
+            // Most frames, nothing changed, so we don't allocate.
+            Some(held) if held.string == string => held.value = cvar.value,
+            _ => *held = Some(Held { value: cvar.value, string: string.into_owned() }),
+        }
+    }
+}
+
+struct Queued {
+    slot: &'static Slot,
+    name: CString,
+    default: CString,
+}
+
+#[derive(Default)]
+struct Cvars {
+    queued: Vec<Queued>,
+
+    // The cvars that the engine added.
+    added: Vec<&'static Slot>,
+}
+
+pub struct Cvar<T> {
+    slot: Slot,
+    value: PhantomData<fn() -> T>,
+}
+
+impl<T: Value> Cvar<T> {
+    pub const fn new(name: &'static str, default: &'static str) -> Cvar<T> {
+        Cvar {
+            slot: Slot {
+                name,
+                default,
+                cvar: AtomicPtr::new(ptr::null_mut()),
+                registered: AtomicBool::new(false),
+                held: Mutex::new(None),
+            },
+            value: PhantomData,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.slot.name
+    }
+
+    // Does nothing if the cvar is already registered, so a feature can call this every time it attaches.
+    pub fn register(&'static self) -> Result<(), Error> {
+        let name = c_name(self.slot.name)?;
+        let default = CString::new(self.slot.default).map_err(|_| Error::Nul(String::from(self.slot.default)))?;
+
+        if self.slot.registered.swap(true, Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        lock().queued.push(Queued { slot: &self.slot, name, default });
+        Ok(())
+    }
+
+    // The value at the last frame. The default until the engine adds the cvar.
+    pub fn get(&self) -> T {
+        match &*self.slot.held() {
+            Some(Held { value, string }) => T::from_cvar(*value, string),
+            None => T::from_cvar(self.slot.default.parse().unwrap_or(0.0), self.slot.default),
+        }
+    }
+}
+
+fn lock() -> MutexGuard<'static, Cvars> {
+    CVARS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// Hands the queued cvars to the engine.
+// A cvar that we added in an earlier load is still there, so we look it up when the engine refuses to add it again.
+pub unsafe fn add_queued() {
+    let queued = mem::take(&mut lock().queued);
+    let funcs = &*ENGINE_FUNCS;
+
+    for Queued { slot, name, default } in queued {
+        // The engine keeps the name and the default for good.
+        let name = name.into_raw();
+        let mut cvar = funcs.pfnRegisterVariable.yank()(name, default.into_raw(), 0);
+
+        if cvar.is_null() {
+            cvar = funcs.pfnGetCvarPointer.yank()(name);
+        }
+
+        if cvar.is_null() {
+            error!("The engine refused to add the cvar {}.", slot.name);
+        } else {
+            slot.cvar.store(cvar, Ordering::Relaxed);
+            lock().added.push(slot);
+            info!("Added the cvar {} at {:?}.", slot.name, cvar);
+        }
+    }
+}
+
+// Copies the value of every cvar that the engine added.
+pub unsafe fn refresh() {
+    for slot in &lock().added {
+        slot.refresh();
+    }
+}
@@ -0,0 +1,79 @@
+// Console commands and cvars that we add to the game, so that features can be configured from its console.
+// Every name starts with `sch_`.
+//
+// The engine's command and cvar lists belong to the game thread, so `register` only queues an entry.
+// The client hook adds the queued entries from HUD_Frame.
+
+use crate::memory;
+
+use std::ffi::CString;
+
+use thiserror::Error;
+
+mod command;
+mod cvar;
+
+pub use command::register;
+pub use cvar::Cvar;
+
+const PREFIX: &str = "sch_";
+
+// BEGIN MUTABLE GLOBAL STATE
+use crate::hook::ENGINE_FUNCS;
+// END MUTABLE GLOBAL STATE
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("\"{0}\" needs to start with \"sch_\"")]
+    Prefix(String),
+
+    #[error("\"{0}\" contains a nul byte")]
+    Nul(String),
+
+    #[error("\"{0}\" is already registered")]
+    Duplicate(String),
+
+    #[error("memory error: {0}")]
+    Memory(#[from] memory::Error),
+}
+
+// Our commands call into this DLL, so this makes them do nothing once we unload.
+pub struct Engine {
+    _commands: command::Stub,
+}
+
+impl Engine {
+    pub fn new() -> Result<Engine, Error> {
+        Ok(Engine {
+            _commands: command::Stub::new()?,
+        })
+    }
+}
+
+// Adds the queued commands and cvars to the engine, and copies the cvar values. Call this on the game thread.
+pub unsafe fn frame() {
+    command::add_queued();
+    cvar::add_queued();
+    cvar::refresh();
+}
+
+// Prints to the game's console.
+pub fn print(text: &str) {
+    let Ok(text) = CString::new(text) else {
+        return;
+    };
+
+    unsafe {
+        if let Some(print) = ENGINE_FUNCS.as_ref().and_then(|funcs| funcs.pfnConsolePrint) {
+            print(text.as_ptr());
+        }
+    }
+}
+
+fn c_name(name: &str) -> Result<CString, Error> {
+    if !name.starts_with(PREFIX) {
+        return Err(Error::Prefix(String::from(name)));
+    }
+
+    CString::new(name).map_err(|_| Error::Nul(String::from(name)))
+}
@@ -0,0 +1,51 @@
+use crate::engine::Cvar;
+use crate::game::usercmd_s;
+
+use super::Feature;
+
+use log::error;
+
+// BEGIN MUTABLE GLOBAL STATE
+use crate::hook::PLAYER_MOVE;
+// END MUTABLE GLOBAL STATE
+
+const IN_JUMP: u16 = 1 << 1;
+const FL_ONGROUND: i32 = 1 << 9;
+
+static IN_WATER: Cvar<bool> = Cvar::new("sch_bunny_hop_in_water", "1");
+
+// Holding jump jumps again as soon as we touch the ground.
+pub struct BunnyHop;
+
+impl Feature for BunnyHop {
+    fn name(&self) -> &'static str {
+        "bunny_hop"
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        false
+    }
+
+    fn on_attach(&mut self) {
+        if let Err(e) = IN_WATER.register() {
+            error!("{e}");
+        }
+    }
+
+    fn on_create_move(&mut self, cmd: &mut usercmd_s) {
+        if cmd.buttons & IN_JUMP != IN_JUMP {
+            return;
+        }
+
+        cmd.buttons &= !IN_JUMP;
+
+        let player_move = unsafe { &*PLAYER_MOVE };
+        let on_ground = player_move.flags & FL_ONGROUND == FL_ONGROUND;
+
+        let swimming = player_move.waterlevel >= 2 && IN_WATER.get();
+
+        if on_ground || swimming {
+            cmd.buttons |= IN_JUMP;
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use crate::game::cl_entity_s;
+use crate::yank::Yank;
+
+use super::Feature;
+
+use std::ptr;
+
+use bstr::BStr;
+use log::info;
+use static_assertions as sa;
+
+const MAX_ENTITIES: i32 = 8192;
+sa::const_assert!(MAX_ENTITIES > 0);
+
+// BEGIN MUTABLE GLOBAL STATE
+static mut ENTITIES: [*mut cl_entity_s; MAX_ENTITIES as usize]  = [ptr::null_mut(); MAX_ENTITIES as usize];
+// END MUTABLE GLOBAL STATE
+
+// Tracks the living model entities that the game adds to the scene.
+pub struct Entities;
+
+impl Feature for Entities {
+    fn name(&self) -> &'static str {
+        "entities"
+    }
+
+    fn on_entity(&mut self, entity: &mut cl_entity_s, model_name: &BStr) {
+        let index = entity.index;
+
+        if index <= 0 || index >= MAX_ENTITIES {
+            return;
+        }
+
+        // We already check for non-positive indices above.
+        // We are casting from a smaller positive domain to a larger positive domain,
+        // so the cast here from i32 -> usize is lossless.
+        #[allow(clippy::cast_sign_loss)]
+        let index = index as usize;
+
+        if !is_model(model_name) {
+            return;
+        }
+
+        let name: &BStr = unsafe {
+            model_name
+                .rsplit(|&byte| byte == b'/')
+                .next()
+                .yank()
+                .into()
+        };
+
+        let alive = entity.is_alive();
+        let entity: *mut cl_entity_s = entity;
+        let slot = unsafe { ENTITIES.get_mut(index).yank() };
+
+        if alive {
+            if slot.is_null() {
+                *slot = entity;
+                info!("Added {entity:?} ({name:?}).");
+            }
+        } else if !slot.is_null() {
+            *slot = ptr::null_mut();
+            info!("Removed {entity:?} ({name:?}).");
+        }
+    }
+
+    fn on_detach(&mut self) {
+        unsafe {
+            ENTITIES.fill(ptr::null_mut());
+        }
+    }
+}
+
+// The index and entity of every tracked entity.
+pub fn tracked() -> Vec<(usize, *mut cl_entity_s)> {
+    unsafe {
+        ENTITIES
+            .iter()
+            .enumerate()
+            .filter(|(_, entity)| !entity.is_null())
+            .map(|(index, &entity)| (index, entity))
+            .collect()
+    }
+}
+
+fn is_model(name: &[u8]) -> bool {
+    const MODELS_SUFFIX: [u8; 4] = *b".mdl";
+    name.ends_with(&MODELS_SUFFIX)
+}
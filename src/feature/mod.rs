@@ -0,0 +1,298 @@
+// Game behavior, split into features that the hooks drive.
+// A feature is a module in this directory with a type that implements `Feature`, plus a line in `builtin()`.
+// The hooks never call a feature directly; they call the dispatch functions below.
+// Off Windows, only the callbacks that don't take game types exist, so that the replay tool can run the features.
+//
+//      pub struct Example;
+//
+//      impl Feature for Example {
+//          fn name(&self) -> &'static str { "example" }
+//          fn on_frame(&mut self, time: f64) { info!("{time}"); }
+//      }
+
+#[cfg(windows)]
+use crate::engine;
+#[cfg(windows)]
+use crate::game::{cl_entity_s, usercmd_s};
+#[cfg(windows)]
+use crate::game::vgui2::Panel;
+
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use bstr::BStr;
+#[cfg(windows)]
+use log::error;
+use log::info;
+use thiserror::Error;
+
+#[cfg(windows)]
+mod bunny_hop;
+#[cfg(windows)]
+pub mod entities;
+
+// BEGIN MUTABLE GLOBAL STATE
+static FEATURES: Mutex<Vec<Entry>> = Mutex::new(Vec::new());
+// END MUTABLE GLOBAL STATE
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("there is no feature named \"{0}\"")]
+    NotFound(String),
+}
+
+// Every callback has an empty default, so a feature only implements what it needs.
+// The callbacks run on the game thread, except for on_attach and on_detach, which run on ours.
+pub trait Feature: Send {
+    fn name(&self) -> &'static str;
+
+    fn enabled_by_default(&self) -> bool {
+        true
+    }
+
+    // When the feature becomes enabled.
+    fn on_attach(&mut self) {}
+
+    // HUD_Frame
+    fn on_frame(&mut self, _time: f64) {}
+
+    // CL_CreateMove, after the game filled in the command.
+    #[cfg(windows)]
+    fn on_create_move(&mut self, _cmd: &mut usercmd_s) {}
+
+    // PaintTraverse, after the game painted `panel`.
+    #[cfg(windows)]
+    fn on_paint(&mut self, _panel: *const Panel) {}
+
+    // HUD_AddEntity
+    #[cfg(windows)]
+    fn on_entity(&mut self, _entity: &mut cl_entity_s, _model_name: &BStr) {}
+
+    // A user message, before the game handles it.
+    fn on_user_msg(&mut self, _name: &BStr, _data: &[u8]) {}
+
+    // When the feature becomes disabled, and when we unload.
+    fn on_detach(&mut self) {}
+}
+
+struct Entry {
+    feature: Box<dyn Feature>,
+    enabled: bool,
+}
+
+#[cfg(windows)]
+fn builtin() -> Vec<Box<dyn Feature>> {
+    vec![
+        Box::new(bunny_hop::BunnyHop),
+        Box::new(entities::Entities),
+    ]
+}
+
+// Every builtin feature needs the game for now.
+#[cfg(not(windows))]
+fn builtin() -> Vec<Box<dyn Feature>> {
+    vec![]
+}
+
+// A panicking feature poisons the lock, but the guard already disabled the hook that it panicked in,
+// so the registry itself is still fine to use.
+fn lock() -> MutexGuard<'static, Vec<Entry>> {
+    FEATURES.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// Registers the builtin features and attaches the ones that are enabled by default.
+// Dropping this detaches every enabled feature.
+pub struct Features;
+
+impl Features {
+    pub fn attach() -> Features {
+        #[cfg(windows)]
+        register_commands();
+
+        register(builtin());
+        Features
+    }
+}
+
+fn register(features: Vec<Box<dyn Feature>>) {
+    let mut entries = lock();
+
+    for feature in features {
+        let mut entry = Entry {
+            enabled: feature.enabled_by_default(),
+            feature,
+        };
+
+        if entry.enabled {
+            entry.feature.on_attach();
+        }
+
+        info!("Registered the {} feature ({}).", entry.feature.name(),
+              if entry.enabled { "enabled" } else { "disabled" });
+
+        entries.push(entry);
+    }
+}
+
+// Lets the game's console enable and disable features too.
+#[cfg(windows)]
+fn register_commands() {
+    let set = |enabled| move |args: &[String]| match args {
+        [name] => {
+            if let Err(e) = set_enabled(name, enabled) {
+                engine::print(&format!("{e}\n"));
+            }
+        }
+
+        _ => engine::print("Expected the name of a feature.\n"),
+    };
+
+    let list = |_: &[String]| {
+        for (name, enabled) in list() {
+            engine::print(&format!("{} {}\n", name, if enabled { "enabled" } else { "disabled" }));
+        }
+    };
+
+    let results = [
+        engine::register("sch_enable", set(true)),
+        engine::register("sch_disable", set(false)),
+        engine::register("sch_features", list),
+    ];
+
+    for result in &results {
+        if let Err(e) = result {
+            error!("Failed to register a feature command: {e}");
+        }
+    }
+}
+
+impl Drop for Features {
+    fn drop(&mut self) {
+        for mut entry in lock().drain(..) {
+            if entry.enabled {
+                entry.feature.on_detach();
+            }
+        }
+
+        info!("Features detached.");
+    }
+}
+
+// Every feature's name and whether it is enabled, in registration order.
+pub fn list() -> Vec<(&'static str, bool)> {
+    lock()
+        .iter()
+        .map(|entry| (entry.feature.name(), entry.enabled))
+        .collect()
+}
+
+pub fn set_enabled(name: &str, enabled: bool) -> Result<(), Error> {
+    let mut entries = lock();
+
+    let entry = entries
+        .iter_mut()
+        .find(|entry| entry.feature.name() == name)
+        .ok_or_else(|| Error::NotFound(String::from(name)))?;
+
+    if entry.enabled != enabled {
+        entry.enabled = enabled;
+
+        if enabled {
+            entry.feature.on_attach();
+        } else {
+            entry.feature.on_detach();
+        }
+    }
+
+    info!("{} the {} feature.", if enabled { "Enabled" } else { "Disabled" }, name);
+    Ok(())
+}
+
+fn each(mut callback: impl FnMut(&mut dyn Feature)) {
+    for entry in lock().iter_mut().filter(|entry| entry.enabled) {
+        callback(entry.feature.as_mut());
+    }
+}
+
+pub fn frame(time: f64) {
+    each(|feature| feature.on_frame(time));
+}
+
+#[cfg(windows)]
+pub fn create_move(cmd: &mut usercmd_s) {
+    each(|feature| feature.on_create_move(cmd));
+}
+
+#[cfg(windows)]
+pub fn paint(panel: *const Panel) {
+    each(|feature| feature.on_paint(panel));
+}
+
+#[cfg(windows)]
+pub fn entity(entity: &mut cl_entity_s, model_name: &BStr) {
+    each(|feature| feature.on_entity(entity, model_name));
+}
+
+pub fn user_msg(name: &BStr, data: &[u8]) {
+    each(|feature| feature.on_user_msg(name, data));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{frame, list, register, set_enabled, user_msg, Error, Feature, Features};
+    use bstr::{BStr, ByteSlice};
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    struct Counter {
+        attached: Arc<AtomicBool>,
+        messages: Arc<AtomicUsize>,
+    }
+
+    impl Feature for Counter {
+        fn name(&self) -> &'static str {
+            "counter"
+        }
+
+        fn on_attach(&mut self) {
+            self.attached.store(true, Ordering::Relaxed);
+        }
+
+        // Other tests may replay messages at the same time, so we only count ours.
+        fn on_user_msg(&mut self, name: &BStr, data: &[u8]) {
+            if (name, data) == (b"Counted".as_bstr(), &[1][..]) {
+                self.messages.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        fn on_detach(&mut self) {
+            self.attached.store(false, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn only_enabled_features_run() {
+        let attached = Arc::new(AtomicBool::new(false));
+        let messages = Arc::new(AtomicUsize::new(0));
+
+        let features = Features::attach();
+        register(vec![Box::new(Counter { attached: Arc::clone(&attached), messages: Arc::clone(&messages) })]);
+        assert_eq!(list(), [("counter", true)]);
+        assert!(attached.load(Ordering::Relaxed));
+
+        user_msg(b"Counted".as_bstr(), &[1]);
+        frame(1.0);
+        assert_eq!(messages.load(Ordering::Relaxed), 1);
+
+        set_enabled("counter", false).unwrap();
+        assert!(!attached.load(Ordering::Relaxed));
+        user_msg(b"Counted".as_bstr(), &[1]);
+        assert_eq!(messages.load(Ordering::Relaxed), 1);
+
+        set_enabled("counter", true).unwrap();
+        assert!(matches!(set_enabled("missing", true), Err(Error::NotFound(name)) if name == "missing"));
+
+        drop(features);
+        assert!(!attached.load(Ordering::Relaxed));
+        assert_eq!(list(), []);
+    }
+}
@@ -1,28 +1,46 @@
 pub use surface::Surface;
 
 pub mod surface {
+    use crate::game::vgui2::Panel;
+
+    use static_assertions as sa;
     use sven_coop_hook_macros::vtable;
-    
+
+    use std::ffi::c_void;
+    use std::os::raw::c_char;
+
     use winapi::ctypes::wchar_t;
 
     pub const INTERFACE: &str = "VGUI_Surface026";
     pub const NUM_VTABLE_ENTRIES: usize = 91;
 
+    pub type HCursor = u32;
+    pub type HTexture = u32;
+    pub type HFont = u32;
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Debug)]
+    pub struct Color {
+        pub r: u8,
+        pub g: u8,
+        pub b: u8,
+        pub a: u8,
+    }
+
     #[repr(C)]
     pub struct Surface {
         vtable: *mut [usize; NUM_VTABLE_ENTRIES],
     }
 
-    impl Surface {
-        vtable! {
-            15 pub set_text_color(r: i32, g: i32, b: i32, a: i32),
-            16 pub set_text_pos(x: i32, y: i32),
-            18 print_text_impl(text: *const wchar_t, len: i32),
-        }
+    // The methods, from surface_virtual_funcs.txt.
+    include!(concat!(env!("OUT_DIR"), "/surface_vtable.rs"));
 
+    sa::const_assert_eq!(NUM_GENERATED_ENTRIES, NUM_VTABLE_ENTRIES);
+
+    impl Surface {
         #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
         pub fn print_text(&self, text: &[u16]) {
-            self.print_text_impl(text.as_ptr(), text.len() as i32);
+            self.draw_print_text(text.as_ptr(), text.len() as i32);
         }
     }
-}
\ No newline at end of file
+}
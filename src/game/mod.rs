@@ -10,13 +10,14 @@ pub mod vgui2;
 
 use std::ffi::CStr;
 use std::iter;
+use std::ptr;
 
 use ultraviolet::Vec3 as vec3_t;
 include!(concat!(env!("OUT_DIR"), "/sdk.rs"));
 
 impl Clone for cl_clientfuncs_s {
     fn clone(&self) -> Self {
-        unsafe { std::ptr::read(self) }
+        unsafe { ptr::read(self) }
     }
 }
 
@@ -40,22 +41,15 @@ impl cl_entity_s {
 }
 
 impl user_msg_s {
-    fn _iter(&self) -> impl Iterator<Item = &Self> {
+    pub fn iter(&self) -> impl Iterator<Item = &Self> {
         iter::successors(Some(self), |current| unsafe { current.next.as_ref() })
     }
 
-    pub fn name(&self) -> &CStr {
-        unsafe { CStr::from_ptr(self.szName.as_ptr()) }
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Self> {
+        iter::successors(Some(self), |current| unsafe { current.next.as_mut() })
     }
 
-    pub fn find<'n>(&mut self, name: &'n str) -> Option<*mut Self> {
-        let mut messages = iter::successors(
-            Some(self),
-            |current| unsafe { current.next.as_mut() }
-        );
-
-        messages
-            .find(|user_msg| user_msg.name().to_bytes() == name.as_bytes())
-            .map(|user_msg| user_msg as *mut _)
+    pub fn name(&self) -> &CStr {
+        unsafe { CStr::from_ptr(self.szName.as_ptr()) }
     }
 }
@@ -16,6 +16,7 @@ pub mod panel {
 
     impl Panel {
         vtable! {
+            size = NUM_VTABLE_ENTRIES;
             36 get_name_impl(panel: *const Panel) -> *const c_char,
         }
 
@@ -0,0 +1,136 @@
+// Keeps a panic in one of our callbacks from taking the game down with it.
+// In debug builds, we catch the panic, disable the hook, and let the game's original function carry on.
+// Release builds abort on panic, so there we only honor the enabled flag.
+
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Mutex;
+
+use log::{error, info};
+
+// BEGIN MUTABLE GLOBAL STATE
+static STATES: Mutex<Vec<&'static HookState>> = Mutex::new(Vec::new());
+// END MUTABLE GLOBAL STATE
+
+pub struct HookState {
+    name: &'static str,
+    enabled: AtomicBool,
+    failures: AtomicU32,
+    registered: AtomicBool,
+}
+
+impl HookState {
+    pub const fn new(name: &'static str) -> HookState {
+        HookState {
+            name,
+            enabled: AtomicBool::new(true),
+            failures: AtomicU32::new(0),
+            registered: AtomicBool::new(false),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+        info!("{} the {} hook.", if enabled { "Enabled" } else { "Disabled" }, self.name);
+    }
+
+    // How many times the hook panicked.
+    pub fn failures(&self) -> u32 {
+        self.failures.load(Ordering::Relaxed)
+    }
+
+    fn register(&'static self) {
+        if !self.registered.swap(true, Ordering::Relaxed) {
+            if let Ok(mut states) = STATES.lock() {
+                states.push(self);
+            }
+        }
+    }
+
+    fn fail(&self, payload: &(dyn Any + Send)) {
+        let failures = self.failures.fetch_add(1, Ordering::Relaxed) + 1;
+        self.enabled.store(false, Ordering::Relaxed);
+
+        error!("The {} hook panicked ({} failures so far) with: {}. Disabled it; the game's original function keeps running.",
+               self.name, failures, describe(payload));
+    }
+}
+
+// Every hook that has run at least once.
+pub fn states() -> Vec<&'static HookState> {
+    STATES.lock().map(|states| states.clone()).unwrap_or_default()
+}
+
+// Runs our part of a callback.
+// Returns None when the hook is disabled or when it panicked, so that the caller can fall back to the original.
+pub fn run<R>(state: &'static HookState, hook: impl FnOnce() -> R) -> Option<R> {
+    state.register();
+
+    if !state.is_enabled() {
+        return None;
+    }
+
+    if cfg!(debug_assertions) {
+        match panic::catch_unwind(AssertUnwindSafe(hook)) {
+            Ok(value) => Some(value),
+            Err(payload) => {
+                state.fail(payload.as_ref());
+                None
+            }
+        }
+    } else {
+        Some(hook())
+    }
+}
+
+// The message of a panic.
+pub fn describe(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "a non-string payload"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{describe, run, states, HookState};
+    use std::panic;
+
+    static STATE: HookState = HookState::new("test::hook");
+
+    #[test]
+    fn a_panicking_hook_is_disabled() {
+        assert_eq!(run(&STATE, || 1), Some(1));
+        assert!(states().iter().any(|state| state.name() == "test::hook"));
+
+        assert_eq!(run(&STATE, || -> i32 { panic!("hook") }), None);
+        assert!(!STATE.is_enabled());
+        assert_eq!(STATE.failures(), 1);
+
+        // A disabled hook doesn't run at all.
+        assert_eq!(run(&STATE, || -> i32 { panic!("hook") }), None);
+        assert_eq!(STATE.failures(), 1);
+
+        STATE.set_enabled(true);
+        assert_eq!(run(&STATE, || 2), Some(2));
+    }
+
+    #[test]
+    fn describes_panics() {
+        assert_eq!(describe(panic::catch_unwind(|| panic!("static")).unwrap_err().as_ref()), "static");
+        assert_eq!(describe(panic::catch_unwind(|| panic!("{}", 7)).unwrap_err().as_ref()), "7");
+        assert_eq!(describe(panic::catch_unwind(|| panic::panic_any(7)).unwrap_err().as_ref()), "a non-string payload");
+    }
+}
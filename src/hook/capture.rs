@@ -0,0 +1,124 @@
+// Records every user message to a capture file, for replaying through the decoders offline.
+// The recorder subscribes first, so it sees the payload before any feature can change it.
+//
+//      > capture session.schc
+//      > capture stop
+
+use crate::dispatch::{Action, SubscriberId};
+use crate::message::{capture, CaptureWriter, Record};
+
+use super::user_msg::{self, Args};
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::os::raw::c_int;
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Instant;
+
+use log::{error, info};
+use once_cell::sync::Lazy;
+use thiserror::Error;
+
+// BEGIN MUTABLE GLOBAL STATE
+static CAPTURE: Lazy<Mutex<Option<Capture>>> = Lazy::new(Mutex::default);
+// END MUTABLE GLOBAL STATE
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("already capturing to {0:?}")]
+    AlreadyCapturing(String),
+
+    #[error("unable to create {path:?}: {source}")]
+    Create {
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[error("{0}")]
+    Capture(#[from] capture::Error),
+}
+
+struct Capture {
+    path: String,
+    writer: CaptureWriter<BufWriter<File>>,
+    start: Instant,
+    records: u64,
+    subscriber: SubscriberId,
+}
+
+fn capture() -> MutexGuard<'static, Option<Capture>> {
+    CAPTURE.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+pub fn start(path: &str) -> Result<(), Error> {
+    let mut capture = capture();
+
+    if let Some(capture) = capture.as_ref() {
+        return Err(Error::AlreadyCapturing(capture.path.clone()));
+    }
+
+    let file = File::create(Path::new(path)).map_err(|source| Error::Create { path: path.to_owned(), source })?;
+    let writer = CaptureWriter::new(BufWriter::new(file))?;
+    let subscriber = user_msg::SUBSCRIBERS.before(i32::MAX, record);
+
+    *capture = Some(Capture {
+        path: path.to_owned(),
+        writer,
+        start: Instant::now(),
+        records: 0,
+        subscriber,
+    });
+
+    info!("Capturing user messages to {path:?}.");
+    Ok(())
+}
+
+// Returns whether we were capturing.
+pub fn stop() -> bool {
+    let capture = capture().take();
+
+    match capture {
+        Some(capture) => {
+            finish(capture);
+            true
+        }
+
+        None => false,
+    }
+}
+
+fn finish(mut capture: Capture) {
+    user_msg::SUBSCRIBERS.remove(capture.subscriber);
+
+    if let Err(e) = capture.writer.flush() {
+        error!("Failed to finish the capture {:?}: {}", capture.path, e);
+    }
+
+    info!("Captured {} user messages to {:?}.", capture.records, capture.path);
+}
+
+fn record(args: &mut Args) -> Action<c_int> {
+    let mut guard = capture();
+
+    // A message may arrive between `stop` taking the capture and removing this subscriber.
+    if let Some(capture) = guard.as_mut() {
+        let record = Record {
+            time: capture.start.elapsed().as_secs_f64(),
+            name: args.name().to_owned(),
+            id: args.id,
+            bytes: args.bytes().to_vec(),
+        };
+
+        match capture.writer.write(&record) {
+            Ok(()) => capture.records += 1,
+
+            Err(e) => {
+                error!("Failed to capture a {:?} message: {}. Stopped capturing.", record.name, e);
+                finish(guard.take().unwrap());
+            }
+        }
+    }
+
+    Action::Continue
+}
@@ -0,0 +1,114 @@
+// A thunk and a list of subscribers for every entry of cl_clientfuncs_s, in client_funcs.txt order.
+//
+//      client_funcs::HUD_Frame::SUBSCRIBERS.before(0, |args| {
+//          info!("HUD_Frame({})", args.time);
+//          Action::Continue
+//      });
+
+// The argument names come from the SDK, so some are close to each other.
+#![allow(non_snake_case, clippy::similar_names)]
+
+use crate::memory::{self, PatchSet};
+
+macro_rules! or_unit {
+    () => { () };
+    ($ty:ty) => { $ty };
+}
+
+macro_rules! client_funcs {
+    ($($field:ident($($arg:ident: $ty:ty),*) $(-> $ret:ty)?;)*) => {
+        $(
+            pub mod $field {
+                // Each entry only needs some of these.
+                #[allow(unused_imports)]
+                use crate::game::*;
+                use crate::dispatch::{Action, Subscribers};
+
+                #[allow(unused_imports)]
+                use std::os::raw::{c_char, c_int, c_uint};
+
+                use sven_coop_hook_macros::hook;
+
+                pub struct Args {
+                    $(pub $arg: $ty,)*
+                }
+
+                pub type Return = or_unit!($($ret)?);
+
+                pub static SUBSCRIBERS: Subscribers<Args, Return> = Subscribers::new();
+
+                #[hook(cl_clientfuncs_s::$field, replace)]
+                pub(super) fn thunk($($arg: $ty),*) $(-> $ret)? {
+                    let mut args = Args { $($arg),* };
+
+                    let mut result = match SUBSCRIBERS.run_before(&mut args) {
+                        Action::Continue => original($(args.$arg),*),
+                        Action::Suppress(value) => value,
+                    };
+
+                    SUBSCRIBERS.run_after(&mut args, &mut result);
+                    result
+                }
+            }
+        )*
+
+        // Points every entry that the game filled in at its thunk.
+        pub unsafe fn install(patches: &mut PatchSet, client_funcs: *mut crate::game::cl_clientfuncs_s)
+            -> Result<(), memory::Error> {
+
+            $(
+                if (*client_funcs).$field.is_some() {
+                    patches.value(&mut (*client_funcs).$field, Some($field::thunk))?;
+                }
+            )*
+
+            Ok(())
+        }
+    };
+}
+
+client_funcs! {
+    Initialize(pEnginefuncs: *mut cl_enginefunc_t, iVersion: c_int) -> c_int;
+    HUD_Init() -> c_int;
+    HUD_VidInit() -> c_int;
+    HUD_Redraw(time: f32, intermission: c_int);
+    HUD_UpdateClientData(pcldata: *mut client_data_t, flTime: f32) -> c_int;
+    HUD_Reset() -> c_int;
+    HUD_PlayerMove(ppmove: *mut playermove_s, server: c_int);
+    HUD_PlayerMoveInit(ppmove: *mut playermove_s);
+    HUD_PlayerMoveTexture(name: *mut c_char) -> c_char;
+    IN_ActivateMouse();
+    IN_DeactivateMouse();
+    IN_MouseEvent(mstate: c_int);
+    IN_ClearStates();
+    IN_Accumulate();
+    CL_CreateMove(frametime: f32, cmd: *mut usercmd_s, active: c_int);
+    CL_IsThirdPerson() -> c_int;
+    CL_CameraOffset(ofs: *mut f32);
+    KB_Find(name: *const c_char) -> *mut kbutton_s;
+    CAM_Think();
+    V_CalcRefdef(pparams: *mut ref_params_s);
+    HUD_AddEntity(typ: c_int, ent: *mut cl_entity_s, modelname: *const c_char) -> c_int;
+    HUD_CreateEntities();
+    HUD_DrawNormalTriangles();
+    HUD_DrawTransparentTriangles();
+    HUD_StudioEvent(event: *const mstudioevent_s, entity: *const cl_entity_s);
+    HUD_PostRunCmd(from: *mut local_state_s, to: *mut local_state_s, cmd: *mut usercmd_s, runfuncs: c_int, time: f64, random_seed: c_uint);
+    HUD_Shutdown();
+    HUD_TxferLocalOverrides(state: *mut entity_state_s, client: *const clientdata_s);
+    HUD_ProcessPlayerState(dst: *mut entity_state_s, src: *const entity_state_s);
+    HUD_TxferPredictionData(ps: *mut entity_state_s, pps: *const entity_state_s, pcd: *mut clientdata_s, ppcd: *const clientdata_s, wd: *mut weapon_data_s, pwd: *const weapon_data_s);
+    Demo_ReadBuffer(size: c_int, buffer: *mut u8);
+    HUD_ConnectionlessPacket(net_from: *mut netadr_s, arguments: *const c_char, response_buffer: *mut c_char, response_buffer_size: *mut c_int) -> c_int;
+    HUD_GetHullBounds(hullnumber: c_int, mins: *mut f32, maxs: *mut f32) -> c_int;
+    HUD_Frame(time: f64);
+    HUD_Key_Event(down: c_int, keynum: c_int, pszCurrentBinding: *const c_char) -> c_int;
+    HUD_TempEntUpdate(frametime: f64, client_time: f64, cl_gravity: f64, ppTempEntFree: *mut *mut tempent_s, ppTempEntActive: *mut *mut tempent_s, Callback_AddVisibleEntity: Option<unsafe extern "C" fn(*mut cl_entity_s) -> c_int>, Callback_TempEntPlaySound: Option<unsafe extern "C" fn(*mut tempent_s, f32)>);
+    HUD_GetUserEntity(index: c_int) -> *mut cl_entity_s;
+    HUD_VoiceStatus(entindex: c_int, bTalking: qboolean) -> c_int;
+    HUD_DirectorMessage(command: u8, firstObject: c_uint, secondObject: c_uint, flags: c_uint) -> c_int;
+    HUD_GetStudioModelInterface(version: c_int, ppinterface: *mut *mut r_studio_interface_s, pstudio: *mut engine_studio_api_s) -> c_int;
+    HUD_CHATINPUTPOSITION_FUNCTION(x: *mut c_int, y: *mut c_int);
+    CLIENTFACTORY();
+    HUD_GETPLAYERTEAM_FUNCTION(iplayer: c_int) -> c_int;
+}
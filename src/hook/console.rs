@@ -0,0 +1,196 @@
+// A line-based shell on the console that we allocated. It runs on the hook thread until `unload`.
+// On start, and on `reload`, it runs the commands in the config file, one per line.
+// Commands that read game memory hand their work to the game thread, which runs it from HUD_Frame.
+// The commands themselves are in crate::console.
+
+use crate::console::{parse, Command, COMMANDS};
+use crate::dispatch::Action;
+use crate::feature::{self, entities};
+use crate::game::user_msg_s;
+use crate::guard;
+
+use super::capture;
+use super::client_funcs::HUD_Frame;
+
+use std::fs;
+use std::io::{self, BufRead};
+use std::mem;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use log::{error, info};
+use once_cell::sync::Lazy;
+
+const CONFIG: &str = "sven_coop_hook.cfg";
+
+// BEGIN MUTABLE GLOBAL STATE
+use crate::hook::USER_MSG;
+static FOR_GAME_THREAD: Lazy<Mutex<Vec<unsafe fn()>>> = Lazy::new(Mutex::default);
+// END MUTABLE GLOBAL STATE
+
+// Whether the shell should keep going after a command.
+enum Flow {
+    Continue,
+    Unload,
+}
+
+// Runs commands from stdin until `unload`, or until stdin closes.
+pub fn run() {
+    let frame = HUD_Frame::SUBSCRIBERS.before(0, |_| {
+        for work in mem::take(&mut *for_game_thread()) {
+            unsafe { work() };
+        }
+
+        Action::Continue
+    });
+
+    shell();
+    HUD_Frame::SUBSCRIBERS.remove(frame);
+}
+
+fn shell() {
+    run_config();
+
+    info!("Type \"help\" for a list of commands.");
+
+    for line in io::stdin().lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                error!("Failed to read a command: {e}");
+                break;
+            }
+        };
+
+        match parse(&line) {
+            Ok(Some(command)) => {
+                if let Flow::Unload = execute(command) {
+                    return;
+                }
+            }
+
+            Ok(None) => {}
+            Err(e) => error!("{e}"),
+        }
+    }
+
+    info!("The console closed. Unloading.");
+}
+
+fn run_config() {
+    let text = match fs::read_to_string(CONFIG) {
+        Ok(text) => text,
+
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            info!("No config at {:?}.", CONFIG);
+            return;
+        }
+
+        Err(e) => {
+            error!("Failed to read the config {:?}: {}", CONFIG, e);
+            return;
+        }
+    };
+
+    info!("Running the config {:?}.", CONFIG);
+
+    for (number, line) in text.lines().enumerate() {
+        match parse(line) {
+            // The config can't unload us, and it can't run itself again.
+            Ok(Some(Command::Unload | Command::Reload)) => {
+                error!("{}:{}: \"{}\" only works in the console.", CONFIG, number + 1, line.trim());
+            }
+
+            Ok(Some(command)) => {
+                execute(command);
+            }
+
+            Ok(None) => {}
+            Err(e) => error!("{}:{}: {}", CONFIG, number + 1, e),
+        }
+    }
+}
+
+fn execute(command: Command) -> Flow {
+    match command {
+        Command::Help => {
+            for (usage, description) in COMMANDS {
+                info!("{usage:<40} {description}");
+            }
+        }
+
+        Command::Hooks => {
+            for state in guard::states() {
+                info!("{:<45} {:<8} {} failures", state.name(),
+                      if state.is_enabled() { "enabled" } else { "disabled" }, state.failures());
+            }
+        }
+
+        Command::Features => {
+            for (name, enabled) in feature::list() {
+                info!("{:<20} {}", name, if enabled { "enabled" } else { "disabled" });
+            }
+        }
+
+        Command::Enable(name) => set_feature(name, true),
+        Command::Disable(name) => set_feature(name, false),
+        Command::Entities => on_game_thread(print_entities),
+        Command::UserMsgs => on_game_thread(print_user_msgs),
+
+        Command::Capture(path) => {
+            if let Err(e) = capture::start(path) {
+                error!("{e}");
+            }
+        }
+
+        Command::StopCapture => {
+            if !capture::stop() {
+                error!("We weren't capturing.");
+            }
+        }
+
+        Command::Log(level) => {
+            log::set_max_level(level);
+            info!("Set the log level to {level}.");
+        }
+
+        Command::Reload => run_config(),
+        Command::Unload => return Flow::Unload,
+    }
+
+    Flow::Continue
+}
+
+fn set_feature(name: &str, enabled: bool) {
+    if let Err(e) = feature::set_enabled(name, enabled) {
+        error!("{e}");
+    }
+}
+
+fn for_game_thread() -> MutexGuard<'static, Vec<unsafe fn()>> {
+    FOR_GAME_THREAD.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// The entity and user message lists belong to the game thread, so we only read them from there.
+fn on_game_thread(work: unsafe fn()) {
+    for_game_thread().push(work);
+}
+
+unsafe fn print_entities() {
+    let tracked = entities::tracked();
+
+    for &(index, entity) in &tracked {
+        let name = (*entity).name().map(|name| name.to_string_lossy());
+        info!("{:>5} {:?} {:?} at {:?}", index, entity, name, (*entity).origin);
+    }
+
+    info!("{} entities.", tracked.len());
+}
+
+unsafe fn print_user_msgs() {
+    let first: Option<&user_msg_s> = USER_MSG.as_ref().and_then(|head| head.as_ref());
+
+    for user_msg in first.into_iter().flat_map(user_msg_s::iter) {
+        info!("{:>3} {:<20} size {:>3} pfn {:#x}", user_msg.iMsg, user_msg.name().to_string_lossy(),
+              user_msg.iSize, user_msg.pfn.map_or(0, |pfn| pfn as usize));
+    }
+}
@@ -1,77 +1,112 @@
+use crate::engine::{self, Engine};
+use crate::feature::Features;
 use crate::game::{cl_clientfuncs_s, cl_enginefuncs_s, playermove_s, user_msg_s};
 use crate::game::hw;
-use crate::idle;
 use crate::memory;
 use crate::module::{self, Module, GameModule};
+use crate::offset_cache::{self, OffsetCache};
+use crate::offsets;
+use crate::resolver;
+use crate::yank::Yank;
 
-use std::mem;
 use std::ptr;
 
 use log::{error, info};
 use thiserror::Error;
 
+mod capture;
 mod client;
+mod client_funcs;
+mod console;
 mod opengl;
 mod panel;
 mod user_msg;
 
+const OFFSET_CACHE: &str = "sven_coop_hook_offsets.txt";
+
 // BEGIN MUTABLE GLOBAL STATE
 pub static mut SURFACE: *const hw::Surface = ptr::null();
 pub static mut ENGINE_FUNCS: *const cl_enginefuncs_s = ptr::null();
 pub static mut ORIGINAL_CLIENT_FUNCS: Option<cl_clientfuncs_s> = None;
 pub static mut PLAYER_MOVE: *const playermove_s = ptr::null();
-pub static mut USER_MSG: *mut user_msg_s = ptr::null_mut(); 
+pub static mut USER_MSG: *mut *mut user_msg_s = ptr::null_mut();
 // END MUTABLE GLOBAL STATE
 
-type Result<T> = std::result::Result<T, Error<'static>>;
+type Result<T> = std::result::Result<T, Error>;
+
+// A table of game functions that we hooked after saving a copy of it.
+// `#[hook]` functions call their originals through this.
+pub trait FunctionTable {
+    unsafe fn originals() -> &'static Self;
+}
+
+impl FunctionTable for cl_clientfuncs_s {
+    unsafe fn originals() -> &'static cl_clientfuncs_s {
+        ORIGINAL_CLIENT_FUNCS.yank_ref()
+    }
+}
 
 #[derive(Error, Debug)]
-pub enum Error<'a> {
+pub enum Error {
+    #[error("engine error: {0}")]
+    Engine(#[from] engine::Error),
+
     #[error("{0}")]
     Module(#[from] module::Error<'static>),
 
     #[error("panel hook error: {0}")]
-    Panel(#[from] panel::Error<'static>),
+    Panel(#[from] panel::Error),
 
-    #[error("could not find address of the string literal \"{0}\"")]
-    NotFoundStringLit(&'a str),
-
-    #[error("bytes not found for {0}")]
-    NotFoundBytes(&'a str),
+    #[error("{0}")]
+    Resolve(#[from] resolver::Error),
 
     #[error("memory error: {0}")]
     Patch(#[from] memory::Error),
 
     #[error("user msg hook error: {0}")]
-    UserMsg(#[from] user_msg::Error<'static>),
+    UserMsg(#[from] user_msg::Error),
 
     #[error("opengl hook error: {0}")]
     OpenGl(#[from] opengl::Error<'static>),
 }
 
 struct Hook {
+    // First, so that it can still restore the user messages from HUD_Frame, which the client hook calls.
+    _user_msg: user_msg::Hook,
+
     _client: client::Hook,
     _opengl: opengl::Hook,
     _panel: panel::Hook,
-    _user_msg: user_msg::Hook,
+    _engine: Engine,
+
+    // Last, so that the features detach after the hooks stop calling them.
+    _features: Features,
 }
 
 impl Hook {
     fn new(modules: &Modules) -> Result<Hook> {
-        let screen_fade = get_screen_fade_instruction(&modules.hw.module)?;
+        let hw = modules.hw.module.resolver_memory();
+        let mut cache = OffsetCache::load(OFFSET_CACHE, offset_cache::Key::from(&modules.hw.module));
 
-        unsafe {
+        let client_funcs = unsafe {
             init_surface(&modules.hw)?;
-            init_engine_funcs(screen_fade)?;
-            init_player_move(screen_fade)?;
-            init_user_msg()?;
+            init_engine_funcs(&hw, &mut cache)?;
+            init_player_move(&hw, &mut cache)?;
+            init_user_msg(&hw, &mut cache)?;
+            find_client_funcs(&hw, &mut cache)?
         };
 
+        if let Err(e) = cache.save() {
+            error!("Failed to save the offset cache: {e}");
+        }
+
         Ok(Hook {
-            _client: unsafe { hook_client_funcs(screen_fade)? },
+            _user_msg: unsafe { user_msg::Hook::new()? },
+            _client: unsafe { hook_client_funcs(client_funcs)? },
             _opengl: unsafe { opengl::Hook::new(&modules.opengl)? },
             _panel: panel::Hook::new(&modules.vgui2)?,
-            _user_msg: unsafe { user_msg::Hook::new()? },
+            _engine: Engine::new()?,
+            _features: Features::attach(),
         })
     }
 }
@@ -93,98 +128,61 @@ impl Modules {
     }
 }
 
-fn get_screen_fade_instruction(hw: &Module) -> Result<*const u8> {
-    const SCREEN_FADE: &str = "ScreenFade";
-    const PUSH: u8 = 0x68;
-
-    let screen_fade = hw
-        .find_string(SCREEN_FADE)
-        .ok_or(Error::NotFoundStringLit(SCREEN_FADE))?;
-
-    let mut push_screen_fade: [u8; 5] = [PUSH, 0, 0, 0, 0];
-    (&mut push_screen_fade[1..])
-        .copy_from_slice(&(screen_fade as usize).to_le_bytes());
-
-    Ok(hw
-        .find_bytes(&push_screen_fade)
-        .ok_or(Error::NotFoundBytes("push ScreenFade instruction"))?)
-}
-
 unsafe fn init_surface(hw: &GameModule) -> Result<()> {
     SURFACE = hw.create_interface::<hw::Surface>(hw::surface::INTERFACE)?;
     info!("SURFACE = {:?}", SURFACE);
     Ok(())
 }
 
-unsafe fn init_engine_funcs(screen_fade: *const u8) -> Result<()> {
-    let engine_funcs: *const *const cl_enginefuncs_s = screen_fade.add(13).cast();
-    ENGINE_FUNCS = engine_funcs.read_unaligned();
+fn is_valid<T>(address: usize) -> bool {
+    memory::ptr_check(address as *const T).is_ok()
+}
+
+unsafe fn init_engine_funcs(hw: &resolver::Memory, cache: &mut OffsetCache) -> Result<()> {
+    ENGINE_FUNCS = cache.resolve(hw, &offsets::ENGINE_FUNCS, is_valid::<cl_enginefuncs_s>)? as *const cl_enginefuncs_s;
     memory::ptr_check(ENGINE_FUNCS)?;
     info!("ENGINE_FUNCS = {:?}", ENGINE_FUNCS);
     Ok(())
 }
 
-unsafe fn init_player_move(screen_fade: *const u8) -> Result<()> {
-    let player_move: *const *const playermove_s = screen_fade.add(36).cast();
-    PLAYER_MOVE = player_move.read_unaligned();
+unsafe fn init_player_move(hw: &resolver::Memory, cache: &mut OffsetCache) -> Result<()> {
+    PLAYER_MOVE = cache.resolve(hw, &offsets::PLAYER_MOVE, is_valid::<playermove_s>)? as *const playermove_s;
     memory::ptr_check(PLAYER_MOVE)?;
     info!("PLAYER_MOVE = {:?}", PLAYER_MOVE);
     Ok(())
 }
 
-unsafe fn init_user_msg() -> Result<()> {
-    /*
-        FF 74 24 08 FF 74 24 08
-        hook_user_msg:  push dword ptr ss:[esp+8]
-                        push dword ptr ss:[esp+8]
-    */
-    let hook_user_msg: *const u8 = mem::transmute((*ENGINE_FUNCS).pfnHookUserMsg.unwrap());
-    
-    /*
-        E8 83 25 01 00
-        call_inner: call hw.421C800
-    */
-    let call_inner_operand: *const *const u8 = hook_user_msg.add(9).cast();
-
-    /*
-        next_instruction:   we only care about the absolute address to resolve the jump of previous call.
-    */
-    let next_instruction = call_inner_operand.add(1) as usize;
-
-    /*
-        53 8B 5C 24 08 55 8B 6C 24 10 56
-        inner_function: push ebx
-                        mov ebx,dword ptr ss:[esp+8]
-                        push ebp
-                        mov ebp,dword ptr ss:[esp+10]
-                        push esi
-    */    
-    let inner_function = call_inner_operand.read_unaligned().add(next_instruction);
-
-    /*
-        8B 35 50 8D B5 04
-        load_head:  mov esi,dword ptr ds:[4B58D50]
-    */
-    let head_of_user_msg_linked_list: *const *const *mut user_msg_s = inner_function.add(13).cast();
-    USER_MSG = head_of_user_msg_linked_list.read_unaligned().read();
+unsafe fn init_user_msg(hw: &resolver::Memory, cache: &mut OffsetCache) -> Result<()> {
+    let hook_user_msg = (*ENGINE_FUNCS).pfnHookUserMsg.unwrap() as usize;
+
+    let recipe = resolver::Recipe {
+        anchor: resolver::Anchor::Address(hook_user_msg),
+        ..offsets::USER_MSG
+    };
+
+    USER_MSG = cache.resolve(hw, &recipe, is_valid::<*mut user_msg_s>)? as *mut *mut user_msg_s;
     memory::ptr_check(USER_MSG)?;
     info!("USER_MSG = {:?}", USER_MSG);
     Ok(())
 }
 
-unsafe fn hook_client_funcs(screen_fade: *const u8) -> Result<client::Hook> {
-    let client_funcs: *const *mut cl_clientfuncs_s = screen_fade.add(19).cast();
-    let client_funcs = client_funcs.read_unaligned();
+unsafe fn find_client_funcs(hw: &resolver::Memory, cache: &mut OffsetCache) -> Result<*mut cl_clientfuncs_s> {
+    let client_funcs = cache.resolve(hw, &offsets::CLIENT_FUNCS, is_valid::<cl_clientfuncs_s>)? as *mut cl_clientfuncs_s;
     memory::ptr_check(client_funcs)?;
-    info!("client_funcs = {:?}", client_funcs);
+    info!("client_funcs = {client_funcs:?}");
+    Ok(client_funcs)
+}
+
+unsafe fn hook_client_funcs(client_funcs: *mut cl_clientfuncs_s) -> Result<client::Hook> {
     ORIGINAL_CLIENT_FUNCS = (*client_funcs).clone().into();
-    Ok(client::Hook::new(client_funcs))
+    Ok(client::Hook::new(client_funcs)?)
 }
 
 pub fn run() -> Result<()> {
     let modules = Modules::new()?;
-    log::info!("{:#x?}", modules);
+    log::info!("{modules:#x?}");
     let _hook = Hook::new(&modules)?;
-    idle();
+    console::run();
+    capture::stop();
     Ok(())
 }
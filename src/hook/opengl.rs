@@ -1,69 +1,62 @@
 use crate::game::GLenum;
+use crate::guard::{self, HookState};
+use crate::inline_hook::{self, InlineHook};
 use crate::module::Module;
 use crate::single_thread_verifier;
 
-use std::ffi::c_void;
 use std::mem;
-use std::ptr;
 
 use bstr::BStr;
-use detours_sys::{DetourAttach, DetourDetach, DetourTransactionBegin, DetourTransactionCommit};
 use log::info;
 use thiserror::Error;
 
 // BEGIN MUTABLE GLOBAL STATE
-static mut ORIGINAL_GL_BEGIN: *mut c_void = ptr::null_mut();
+static mut ORIGINAL_GL_BEGIN: usize = 0;
 // END MUTABLE GLOBAL STATE
 
 #[derive(Error, Debug)]
 pub enum Error<'a> {
     #[error("Unable to find the address of {0}")]
-    GetProcAddress(&'a BStr)
+    GetProcAddress(&'a BStr),
+
+    #[error("failed to hook {0}: {1}")]
+    InlineHook(&'a BStr, inline_hook::Error),
 }
 
-pub struct Hook {}
+pub struct Hook {
+    _gl_begin: InlineHook,
+}
 
 impl Hook {
     pub unsafe fn new(opengl: &Module) -> Result<Self, Error<'static>> {
-        add_detours(opengl)?;
-        Ok(Self {})
-    }
-}
-
-impl Drop for Hook {
-    fn drop(&mut self) {
-        unsafe {
-            remove_detours();
-        }
+        Ok(Self {
+            _gl_begin: hook_gl_begin(opengl)?,
+        })
     }
 }
 
 unsafe extern "system" fn my_gl_begin(mode: GLenum) {
     type GlBegin = unsafe extern "system" fn (mode: GLenum);
+    static STATE: HookState = HookState::new("glBegin");
+
+    guard::run(&STATE, single_thread_verifier::assert);
     
-    single_thread_verifier::assert();
-    
-    let original = mem::transmute::<*mut c_void, GlBegin>(ORIGINAL_GL_BEGIN);
+    let original = mem::transmute::<usize, GlBegin>(ORIGINAL_GL_BEGIN);
     original(mode);
 }
 
-unsafe fn add_detours(opengl: &Module) -> Result<(), Error<'static>> {
+unsafe fn hook_gl_begin(opengl: &Module) -> Result<InlineHook, Error<'static>> {
     const GL_BEGIN: [u8; 8] = *b"glBegin\0";
 
-    ORIGINAL_GL_BEGIN = opengl.get_proc_address(&GL_BEGIN)
-        .ok_or_else(|| Error::GetProcAddress(GL_BEGIN.as_ref().into()))? as *mut c_void;
-    
-    info!("ORIGINAL_GL_BEGIN={:?}", ORIGINAL_GL_BEGIN);
-    // todo: Check for error codes and bubble up.
-    DetourTransactionBegin();
-    DetourAttach(&mut ORIGINAL_GL_BEGIN, my_gl_begin as *mut _);
-    DetourTransactionCommit();
+    let gl_begin = opengl.get_proc_address(&GL_BEGIN)
+        .ok_or_else(|| Error::GetProcAddress(GL_BEGIN.as_ref().into()))?;
 
-    Ok(())
-}
+    let hook_error = |e: inline_hook::Error| Error::InlineHook(GL_BEGIN.as_ref().into(), e);
 
-unsafe fn remove_detours() {
-    DetourTransactionBegin();
-    DetourDetach(&mut ORIGINAL_GL_BEGIN, my_gl_begin as *mut _);
-    DetourTransactionCommit();
-}
\ No newline at end of file
+    let mut hook = InlineHook::new(gl_begin, my_gl_begin as usize).map_err(hook_error)?;
+    ORIGINAL_GL_BEGIN = hook.trampoline();
+    hook.enable().map_err(hook_error)?;
+
+    info!("glBegin={:#x} ORIGINAL_GL_BEGIN={:#x}", gl_begin, ORIGINAL_GL_BEGIN);
+    Ok(hook)
+}
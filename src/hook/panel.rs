@@ -1,89 +1,62 @@
-use crate::memory::{self, Patch};
+use crate::feature;
 use crate::module::{self, GameModule};
-use crate::game::vgui2;
+use crate::game::vgui2::{self, Panel};
+use crate::guard::{self, HookState};
+use crate::vtable::{self, Slot, VtableHook};
 
-use std::mem::{self, ManuallyDrop};
-
-use log::{error, info};
+use log::info;
 use thiserror::Error;
 
+type PaintTraverse = unsafe extern "fastcall" fn(this: *const Panel, edx: usize, panel: *const Panel, force_repaint: bool, allow_force: bool);
+const PAINT_TRAVERSE: Slot<PaintTraverse> = Slot::new(41);
+
 // BEGIN MUTABLE GLOBAL STATE
-static mut OLD_PAINT_TRAVERSE: usize = 0;
+static mut OLD_PAINT_TRAVERSE: Option<PaintTraverse> = None;
 // END MUTABLE GLOBAL STATE
 
 #[derive(Error, Debug)]
-pub enum Error<'a> {
+pub enum Error {
     #[error("{0}")]
     Module(#[from] module::Error<'static>),
 
-    #[error("interface \"{0}\" has a null vtable")]
-    NullVtable(&'a str),
-
-    #[error("patch error: {0}")]
-    Patch(#[from] memory::Error),
+    #[error("{0}")]
+    Vtable(#[from] vtable::Error),
 }
 
 pub struct Hook {
-    vtable_patch: Patch<*mut [usize; vgui2::panel::NUM_VTABLE_ENTRIES]>,
-    modified_vtable: ManuallyDrop<Box<[usize; vgui2::panel::NUM_VTABLE_ENTRIES]>>,
+    _vtable: VtableHook<Panel, { vgui2::panel::NUM_VTABLE_ENTRIES }>,
 }
 
 impl Drop for Hook {
     fn drop(&mut self) {
-        unsafe {
-            self.vtable_patch.restore();
-            
-            // Only drop the modified panel vtable after we restore the original vtable;
-            // otherwise, panel will access deallocated vtable entries.
-            ManuallyDrop::drop(&mut self.modified_vtable);
-        }
-
         info!("Panel hook dropped.");
     }
 }
 
 impl Hook {
-    pub fn new(vgui2: &GameModule) -> Result<Hook, Error<'static>> {
-        let panel = vgui2.create_interface::<vgui2::Panel>(vgui2::panel::INTERFACE)?;
+    pub fn new(vgui2: &GameModule) -> Result<Hook, Error> {
+        let panel = vgui2.create_interface::<Panel>(vgui2::panel::INTERFACE)?;
 
-        info!("panel = {:#x?}", panel);
-    
-        if unsafe { (*panel).vtable.is_null() } {
-            return Err(Error::NullVtable(vgui2::panel::INTERFACE));
-        }
-    
-        let mut modified_vtable = {
-            unsafe { 
-                // Copy the original Panel vtable to our vtable.
-                let mut vtable = Box::new(*(*panel).vtable);
+        info!("panel = {panel:#x?}");
 
-                // Hook PaintTraverse and save the original.
-                OLD_PAINT_TRAVERSE = mem::replace(
-                    &mut vtable[41],
-                    my_paint_traverse as usize,
-                );
+        let mut vtable = unsafe { VtableHook::new(panel)? };
+        vtable.hook(PAINT_TRAVERSE, my_paint_traverse)?;
 
-                ManuallyDrop::new(vtable)
-            }
-        };
-    
-        // Replace Panel vtable with our modified vtable.
-        let vtable_patch = unsafe {
-            Patch::new(
-                &mut (*panel).vtable,
-                modified_vtable.as_mut()
-            )
-        }?;
+        unsafe {
+            OLD_PAINT_TRAVERSE = Some(vtable.original(PAINT_TRAVERSE)?);
+            vtable.enable()?;
+        }
 
-        Ok(Hook {
-            modified_vtable,
-            vtable_patch,
-        })
+        Ok(Hook { _vtable: vtable })
     }
 }
 
-unsafe extern "fastcall" fn my_paint_traverse(this: *const vgui2::Panel, edx: usize, panel: *const vgui2::Panel, force_repaint: bool, allow_force: bool) {
-    type PaintTraverse = extern "fastcall" fn(*const vgui2::Panel, usize, *const vgui2::Panel, bool, bool);
-    let original: PaintTraverse = mem::transmute(OLD_PAINT_TRAVERSE);
-    original(this, edx, panel, force_repaint, allow_force);
-}
\ No newline at end of file
+unsafe extern "fastcall" fn my_paint_traverse(this: *const Panel, edx: usize, panel: *const Panel, force_repaint: bool, allow_force: bool) {
+    static STATE: HookState = HookState::new("Panel::PaintTraverse");
+
+    if let Some(original) = OLD_PAINT_TRAVERSE {
+        original(this, edx, panel, force_repaint, allow_force);
+    }
+
+    guard::run(&STATE, || feature::paint(panel));
+}
@@ -1,95 +1,318 @@
-use crate::game::pfnUserMsgHook;
+// Hook-all mode: every user message goes through `on_user_msg`, which finds the original by the name that the
+// engine passes it. We install `on_user_msg` on a message as soon as the game registers it through pfnHookUserMsg,
+// and every HUD_Frame we catch the messages that the game re-hooked by writing the list directly.
+// Setting sch_user_msg_wrap to 0 stops the installing, but we still record every registration.
+//
+//      user_msg::SUBSCRIBERS.before(0, |args| {
+//          if let Ok(Message::Health(health)) = args.decode() {
+//              info!("{} health", health.value);
+//          }
+//
+//          Action::Continue
+//      });
 
-use std::os::raw::c_void;
+use crate::dispatch::{Action, SubscriberId, Subscribers};
+use crate::engine::{self, Cvar};
+use crate::feature;
+use crate::game::{pfnUserMsgHook, user_msg_s};
+use crate::guard::{self, HookState};
+use crate::inline_hook::{self, InlineHook};
+use crate::message::{self, DecodeError, Message, MsgReader};
 
-use log::{error, info};
+use super::client_funcs::HUD_Frame;
+
+use std::cell::Cell;
+use std::ffi::CStr;
+use std::mem;
+use std::os::raw::{c_char, c_int, c_void};
+use std::ptr;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bstr::{BStr, ByteSlice};
+use log::{error, info, warn};
+use once_cell::sync::Lazy;
+use rustc_hash::FxHashMap;
 use thiserror::Error;
 
 // BEGIN MUTABLE GLOBAL STATE
-use crate::hook::USER_MSG;
+use crate::hook::{ENGINE_FUNCS, USER_MSG};
+static mut ORIGINAL_HOOK_USER_MSG: usize = 0;
+static REGISTRY: Lazy<Mutex<FxHashMap<Vec<u8>, Registration>>> = Lazy::new(Mutex::default);
+pub static SUBSCRIBERS: Subscribers<Args, c_int> = Subscribers::new();
 // END MUTABLE GLOBAL STATE
 
+static WRAP: Cvar<bool> = Cvar::new("sch_user_msg_wrap", "1");
+
+// How long we wait for a HUD_Frame to restore the messages in, before we restore them from our thread.
+const RESTORE_TIMEOUT: Duration = Duration::from_secs(2);
+
+type HookUserMsg = unsafe extern "C" fn(name: *mut c_char, pfn: pfnUserMsgHook) -> c_int;
+
 #[derive(Error, Debug)]
-pub enum Error<'a> {
-    #[error("unable to find the user_msg_s for \"{0}\"")]
-    MsgNotFound(&'a str),
+pub enum Error {
+    #[error("failed to hook pfnHookUserMsg: {0}")]
+    InlineHook(#[from] inline_hook::Error),
+
+    #[error("{0}")]
+    Engine(#[from] engine::Error),
+}
+
+// The arguments of a pfnUserMsgHook.
+pub struct Args {
+    pub name: *const c_char,
+    pub size: c_int,
+    pub buf: *mut c_void,
+
+    // Not an argument; the iMsg that the server assigned to the message, or 0 if we don't know it.
+    pub id: c_int,
+}
+
+impl Args {
+    pub fn name(&self) -> &BStr {
+        unsafe { CStr::from_ptr(self.name) }.to_bytes().into()
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        unsafe { MsgReader::from_raw(self.buf, self.size) }.map_or(&[], |mut reader| reader.read_rest())
+    }
+
+    pub fn decode(&self) -> Result<Message, DecodeError> {
+        message::decode(self.name(), self.bytes())
+    }
+}
+
+// The game's own function for a message, and where the message lives.
+// We record every message that the game hooks, whether or not we wrap it.
+#[derive(Clone, Copy)]
+struct Registration {
+    user_msg: usize,
+    pfn: pfnUserMsgHook,
+
+    // Whether `on_user_msg` took the place of `pfn`, so that `restore` puts `pfn` back.
+    wrapped: bool,
+}
+
+fn registry() -> MutexGuard<'static, FxHashMap<Vec<u8>, Registration>> {
+    REGISTRY.lock().unwrap_or_else(PoisonError::into_inner)
 }
 
 pub struct Hook {
+    frame: SubscriberId,
+    features: SubscriberId,
+    registrations: InlineHook,
 }
 
 impl Hook {
-    pub unsafe fn new() -> Result<Self, Error<'static>> {
-        Ok(Self {
-        })
+    pub unsafe fn new() -> Result<Self, Error> {
+        WRAP.register()?;
+
+        let registrations = hook_hook_user_msg()?;
+
+        // We only write to the list on the game thread.
+        let frame = HUD_Frame::SUBSCRIBERS.before(0, |_| {
+            install();
+            Action::Continue
+        });
+
+        let features = SUBSCRIBERS.before(0, |args| {
+            feature::user_msg(args.name(), args.bytes());
+            Action::Continue
+        });
+
+        Ok(Self { frame, features, registrations })
+    }
+}
+
+impl Drop for Hook {
+    fn drop(&mut self) {
+        // So that the game can't register a message that we wrap after we restore the rest.
+        if let Err(e) = unsafe { self.registrations.disable() } {
+            error!("Failed to unhook pfnHookUserMsg: {e}");
+        }
+
+        HUD_Frame::SUBSCRIBERS.remove(self.frame);
+        SUBSCRIBERS.remove(self.features);
+
+        // The game thread calls through the list, so we restore it from HUD_Frame.
+        // The lock makes sure that exactly one of the threads restores, and that it's done when we go on.
+        let restored = Arc::new(Mutex::new(false));
+
+        let restore_frame = HUD_Frame::SUBSCRIBERS.before(i32::MAX, {
+            let restored = Arc::clone(&restored);
+
+            move |_| {
+                restore_once(&restored);
+                Action::Continue
+            }
+        });
+
+        let start = Instant::now();
+
+        while !*lock(&restored) && start.elapsed() < RESTORE_TIMEOUT {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        HUD_Frame::SUBSCRIBERS.remove(restore_frame);
+
+        if !*lock(&restored) {
+            warn!("No frame came in {:?}, so we're restoring the user messages from our thread.", RESTORE_TIMEOUT);
+            restore_once(&restored);
+        }
+
+        info!("User msg hook dropped.");
     }
 }
 
-struct Single {
-    name: &'static str,
-    original: &'static mut pfnUserMsgHook,
+fn lock(restored: &Mutex<bool>) -> MutexGuard<bool> {
+    restored.lock().unwrap_or_else(PoisonError::into_inner)
 }
 
-impl Single {
-    unsafe fn _new(name: &'static str, original: &'static mut pfnUserMsgHook,
-                  new: pfnUserMsgHook) -> Result<Single, Error<'static>> {
+fn restore_once(restored: &Mutex<bool>) {
+    let mut restored = lock(restored);
 
-        *original = hook(name, new)?;
+    if !*restored {
+        unsafe { restore() };
+        *restored = true;
+    }
+}
 
-        Ok(Single {
-            name,
-            original,
-        })
+// Puts back the functions that we replaced.
+unsafe fn restore() {
+    for (name, registration) in registry().iter_mut().filter(|(_, registration)| registration.wrapped) {
+        let user_msg = registration.user_msg as *mut user_msg_s;
+        registration.wrapped = false;
+
+        if is_ours((*user_msg).pfn) {
+            (*user_msg).pfn = registration.pfn;
+        } else {
+            warn!("The game re-hooked the user message {:?}, so we left it alone.", name.as_bstr());
+        }
     }
 }
 
-impl Drop for Single {
-    fn drop(&mut self) {
-        unsafe {
-            let _ = hook(self.name, *self.original);
+fn is_ours(pfn: pfnUserMsgHook) -> bool {
+    pfn.map(|pfn| pfn as usize) == Some(on_user_msg as usize)
+}
+
+unsafe fn install() {
+    if !WRAP.get() {
+        return;
+    }
+
+    let mut registry = registry();
+
+    // A function that isn't ours is one that the game wrote to the list without pfnHookUserMsg.
+    for user_msg in (*USER_MSG).as_mut().into_iter().flat_map(user_msg_s::iter_mut) {
+        if !is_ours(user_msg.pfn) {
+            let pfn = user_msg.pfn;
+            let registration = record(&mut registry, user_msg, pfn);
+            wrap(user_msg, registration);
         }
     }
 }
 
-unsafe fn hook(message_name: &str, hook: pfnUserMsgHook) -> Result<pfnUserMsgHook, Error> {
-    let user_msg = (*USER_MSG).find(message_name).ok_or(Error::MsgNotFound(message_name))?;
+// Records that the message now has the game's function `pfn`.
+fn record<'r>(registry: &'r mut FxHashMap<Vec<u8>, Registration>, user_msg: &mut user_msg_s,
+              pfn: pfnUserMsgHook) -> &'r mut Registration {
+
+    let registration = Registration { user_msg: ptr::from_mut(user_msg) as usize, pfn, wrapped: false };
+    let slot = registry.entry(user_msg.name().to_bytes().to_vec()).or_insert(registration);
+    *slot = registration;
+    slot
+}
+
+// Puts `on_user_msg` in place of the game's function that we recorded for the message.
+fn wrap(user_msg: &mut user_msg_s, registration: &mut Registration) {
+    info!("Hooked the user message {:?}. The original function is at {:#x}.",
+          user_msg.name().to_bytes().as_bstr(), registration.pfn.map_or(0, |pfn| pfn as usize));
+
+    user_msg.pfn = Some(on_user_msg);
+    registration.wrapped = true;
+}
 
-    // TODO: Investigate.
-    // We are setting `pfn` in our hook thread.
-    // The game may be accessing `pfn` in its game thread.
-    // What are the possible reprecussions of these two actions?
-    // Setting a usize is an atomic operation, but what if the game does the following sequence:
+unsafe fn hook_hook_user_msg() -> Result<InlineHook, Error> {
+    let hook_user_msg = (*ENGINE_FUNCS).pfnHookUserMsg.unwrap() as usize;
 
-    // A = load(pfn)
-    // ...other operations...
-    // B = load(pfn)
-    // code that assumes A == B
+    let mut hook = InlineHook::new(hook_user_msg, on_hook_user_msg as usize)?;
+    let trampoline = hook.trampoline();
+    ORIGINAL_HOOK_USER_MSG = trampoline;
+    hook.enable()?;
+
+    info!("pfnHookUserMsg={hook_user_msg:#x} ORIGINAL_HOOK_USER_MSG={trampoline:#x}");
+    Ok(hook)
+}
 
-    // Our hook's thread may run concurrent to the thread running "...other operations..." so that
-    // B == our hooked function instead of the original function.
-    // A != B, breaking a previous invariant.
+// The engine adds the message to the front of the list, or replaces the function of the message with that name.
+// Either way, we let it, and then look the message up.
+unsafe extern "C" fn on_hook_user_msg(name: *mut c_char, pfn: pfnUserMsgHook) -> c_int {
+    static STATE: HookState = HookState::new("pfnHookUserMsg");
 
-    // We can't really "inject" a mutex or synchronization primitive in the game.
-    // SuspendThread + ResumeThread? 
-    let original = (*user_msg).pfn;
-    (*user_msg).pfn = hook;
+    crate::single_thread_verifier::assert();
 
-    info!("Found user_msg_s \"{}\" at {:?}. The original function is at {:#x}.",
-          message_name, user_msg, original.unwrap() as usize);
-    
-    Ok(original)
+    let original = mem::transmute::<usize, HookUserMsg>(ORIGINAL_HOOK_USER_MSG);
+    let result = original(name, pfn);
+
+    guard::run(&STATE, || registered(CStr::from_ptr(name).to_bytes(), pfn));
+    result
 }
 
-unsafe fn _print_buffer(size: i32, buf: *mut c_void) {
-    if size <= 0 {
-        error!("Buffer has a non-positive size of {} bytes.", size);
+unsafe fn registered(name: &[u8], pfn: pfnUserMsgHook) {
+    let user_msg = (*USER_MSG)
+        .as_mut()
+        .into_iter()
+        .flat_map(user_msg_s::iter_mut)
+        .find(|user_msg| user_msg.name().to_bytes() == name);
+
+    let Some(user_msg) = user_msg else {
+        warn!("The game registered the user message {:?}, but it isn't in the list.", name.as_bstr());
         return;
+    };
+
+    info!("The game registered the user message {:?} with the function {:#x}.",
+          name.as_bstr(), pfn.map_or(0, |pfn| pfn as usize));
+
+    let mut registry = registry();
+    let registration = record(&mut registry, user_msg, pfn);
+
+    if WRAP.get() {
+        wrap(user_msg, registration);
     }
+}
+
+unsafe extern "C" fn on_user_msg(name: *const c_char, size: c_int, buf: *mut c_void) -> c_int {
+    static STATE: HookState = HookState::new("user_msg");
+
+    crate::single_thread_verifier::assert();
+
+    let (original, id) = registry()
+        .get(CStr::from_ptr(name).to_bytes())
+        .map_or((None, 0), |registration| (registration.pfn, (*(registration.user_msg as *const user_msg_s)).iMsg));
+
+    // What the original returned, if it ran, so that a panic after it doesn't run it again.
+    let returned = Cell::new(None);
+
+    // The engine doesn't require a message to have a function, so neither do we.
+    let call_original = |args: &Args| {
+        let value = original.map_or(0, |original| original(args.name, args.size, args.buf));
+        returned.set(Some(value));
+        value
+    };
+
+    let result = guard::run(&STATE, || {
+        let mut args = Args { name, size, buf, id };
+
+        let mut result = match SUBSCRIBERS.run_before(&mut args) {
+            Action::Continue => call_original(&args),
+            Action::Suppress(value) => value,
+        };
 
-    #[allow(clippy::cast_sign_loss)]
-    let size = size as usize;
+        SUBSCRIBERS.run_after(&mut args, &mut result);
+        result
+    });
 
-    let buf: *const u8 = buf.cast();
-    let buf = std::slice::from_raw_parts(buf, size);
-    info!("{:?}", buf);
+    result
+        .or_else(|| returned.get())
+        .unwrap_or_else(|| call_original(&Args { name, size, buf, id }))
 }
@@ -0,0 +1,357 @@
+// Detours a function by overwriting its first instructions with a jmp.
+// The trampoline runs the instructions that we overwrote and then jumps to the rest of the original function.
+
+use crate::disasm::{self, Map, MAX_INSTRUCTION_LEN};
+use crate::memory::{self, ExecutableMemory, PatchSet};
+
+use std::mem::ManuallyDrop;
+use std::slice;
+
+use log::error;
+use thiserror::Error;
+
+// E9 rel32: jmp rel32
+const JMP_REL32: u8 = 0xE9;
+pub const JMP_LEN: usize = 5;
+
+// CC: int3
+// Pads the stolen bytes that follow the jmp, so that stray execution traps instead of running half an instruction.
+const INT3: u8 = 0xCC;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("failed to decode the prologue: {0}")]
+    Decode(#[from] disasm::Error),
+
+    #[error("can't relocate {bytes:02X?} at {address:#x} because only rel32 branches can move")]
+    UnsupportedRelative {
+        address: usize,
+        bytes: Vec<u8>,
+    },
+
+    #[error("{bytes:02X?} at {address:#x} branches to {target:#x}, which the jmp overwrites")]
+    BranchIntoPatch {
+        address: usize,
+        target: usize,
+        bytes: Vec<u8>,
+    },
+
+    #[error("the function at {address:#x} ends after {len} bytes, which is too short for a jmp")]
+    TooShort {
+        address: usize,
+        len: usize,
+    },
+
+    #[error("can't read the code at {0:#x}")]
+    Unreadable(usize),
+
+    #[error("{0}")]
+    Memory(#[from] memory::Error),
+}
+
+pub struct Trampoline {
+    pub bytes: Vec<u8>,
+
+    // How many bytes of the original function the trampoline took over. The hook overwrites exactly these bytes.
+    pub stolen: usize,
+}
+
+// Builds the trampoline for the function at `source` whose code starts with `code`,
+// as if the trampoline will sit at `destination`. This only transforms bytes.
+pub fn build_trampoline(code: &[u8], source: usize, destination: usize) -> Result<Trampoline, Error> {
+    let mut bytes = vec![];
+    let mut stolen = 0;
+    let mut branches = vec![];
+
+    while stolen < JMP_LEN {
+        let instruction = disasm::decode(&code[stolen..], source + stolen)?;
+        let mut relocated = instruction.bytes().to_vec();
+
+        if let (Some(field), Some(target)) = (instruction.relative, instruction.target()) {
+            if field.size != 4 {
+                return Err(Error::UnsupportedRelative {
+                    address: instruction.address,
+                    bytes: relocated,
+                });
+            }
+
+            let next = destination + bytes.len() + instruction.len;
+            relocated[field.offset..field.offset + 4].copy_from_slice(&rel32(next, target));
+            branches.push((instruction, target));
+        }
+
+        bytes.extend_from_slice(&relocated);
+        stolen += instruction.len;
+
+        let ends_function = instruction.opcode.map == Map::OneByte && match instruction.opcode.byte {
+            0xC2 | 0xC3 | 0xCC | JMP_REL32 => true,
+            0xFF => instruction.modrm.is_some_and(|modrm| modrm.reg == 4 || modrm.reg == 5),
+            _ => false,
+        };
+
+        if ends_function && stolen < JMP_LEN {
+            return Err(Error::TooShort { address: source, len: stolen });
+        }
+    }
+
+    for (instruction, target) in branches {
+        if source < target && target < source + stolen {
+            return Err(Error::BranchIntoPatch {
+                address: instruction.address,
+                target,
+                bytes: instruction.bytes().to_vec(),
+            });
+        }
+    }
+
+    bytes.extend_from_slice(&jmp(destination + bytes.len(), source + stolen));
+
+    Ok(Trampoline { bytes, stolen })
+}
+
+pub fn jmp(from: usize, to: usize) -> [u8; JMP_LEN] {
+    let [b0, b1, b2, b3] = rel32(from + JMP_LEN, to);
+    [JMP_REL32, b0, b1, b2, b3]
+}
+
+// The rel32 that an instruction ending at `next` needs to reach `target`.
+#[allow(clippy::cast_possible_truncation)]
+fn rel32(next: usize, target: usize) -> [u8; 4] {
+    (target as u32).wrapping_sub(next as u32).to_le_bytes()
+}
+
+// Sends calls of `target` to `detour`. Call `trampoline()` to run the original function.
+//
+//      let mut hook = InlineHook::new(gl_begin, my_gl_begin as usize)?;
+//      ORIGINAL_GL_BEGIN = hook.trampoline();
+//      hook.enable()?;
+//
+// We don't suspend other threads while we write the jmp.
+pub struct InlineHook {
+    target: usize,
+    patch: PatchSet,
+    trampoline: ManuallyDrop<ExecutableMemory>,
+}
+
+impl InlineHook {
+    pub unsafe fn new(target: usize, detour: usize) -> Result<InlineHook, Error> {
+        // The last stolen instruction starts before JMP_LEN.
+        // A short function may sit at the end of its pages, so we stop where we can't read.
+        let len = memory::readable_len(target as *const u8, JMP_LEN - 1 + MAX_INSTRUCTION_LEN);
+
+        if len < JMP_LEN {
+            return Err(Error::Unreadable(target));
+        }
+
+        let code = slice::from_raw_parts(target as *const u8, len);
+
+        let mut trampoline = ExecutableMemory::new(code.len() + JMP_LEN)?;
+        let built = build_trampoline(code, target, trampoline.address())?;
+        trampoline.as_mut_slice()[..built.bytes.len()].copy_from_slice(&built.bytes);
+
+        let mut overwrite = vec![INT3; built.stolen];
+        overwrite[..JMP_LEN].copy_from_slice(&jmp(target, detour));
+
+        let mut patch = PatchSet::new();
+        patch.bytes(target as *mut u8, &overwrite)?;
+
+        Ok(InlineHook {
+            target,
+            patch,
+            trampoline: ManuallyDrop::new(trampoline),
+        })
+    }
+
+    pub fn target(&self) -> usize {
+        self.target
+    }
+
+    pub fn trampoline(&self) -> usize {
+        self.trampoline.address()
+    }
+
+    pub unsafe fn enable(&mut self) -> Result<(), Error> {
+        Ok(self.patch.apply()?)
+    }
+
+    pub unsafe fn disable(&mut self) -> Result<(), Error> {
+        Ok(self.patch.restore()?)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.patch.is_applied()
+    }
+}
+
+// We never free the trampoline. Another thread may be inside it, or inside a detour that is about to call it,
+// even after we put the original bytes back.
+impl Drop for InlineHook {
+    fn drop(&mut self) {
+        if let Err(e) = unsafe { self.disable() } {
+            error!("Failed to unhook {:#x}. {}", self.target, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{build_trampoline, jmp, Error, INT3};
+    use crate::disasm;
+
+    const SOURCE: usize = 0x1000_0000;
+    const DESTINATION: usize = 0x2000_0000;
+
+    #[test]
+    fn copies_whole_instructions_and_jumps_back() {
+        let code = [
+            0x55,                   // push ebp
+            0x8B, 0xEC,             // mov ebp,esp
+            0x83, 0xEC, 0x10,       // sub esp,10
+            0x56,                   // push esi
+        ];
+
+        let trampoline = build_trampoline(&code, SOURCE, DESTINATION).unwrap();
+        assert_eq!(trampoline.stolen, 6);
+        assert_eq!(trampoline.bytes[..6], code[..6]);
+        assert_eq!(trampoline.bytes[6..], jmp(DESTINATION + 6, SOURCE + 6));
+        assert_eq!(trampoline.bytes[6..], [0xE9, 0xFB, 0xFF, 0xFF, 0xEF]);
+    }
+
+    #[test]
+    fn relocates_rel32_branches() {
+        let code = [
+            0x6A, 0x07,                     // push 7
+            0xE8, 0x10, 0x00, 0x00, 0x00,   // call SOURCE + 0x17
+            0xC3,                           // ret
+        ];
+
+        let trampoline = build_trampoline(&code, SOURCE, DESTINATION).unwrap();
+        assert_eq!(trampoline.stolen, 7);
+        assert_eq!(trampoline.bytes[..3], code[..3]);
+
+        let call = disasm::decode(&trampoline.bytes[2..], DESTINATION + 2).unwrap();
+        assert_eq!(call.target(), Some(SOURCE + 0x17));
+        assert_eq!(trampoline.bytes[7..], jmp(DESTINATION + 7, SOURCE + 7));
+
+        // A jcc with a rel32 moves too.
+        let code = [0x0F, 0x84, 0x00, 0x01, 0x00, 0x00, 0x90];
+        let trampoline = build_trampoline(&code, SOURCE, DESTINATION).unwrap();
+        let je = disasm::decode(&trampoline.bytes, DESTINATION).unwrap();
+        assert_eq!(je.target(), Some(SOURCE + 0x106));
+    }
+
+    #[test]
+    fn relocated_branches_wrap_around_the_address_space() {
+        let code = [0xE9, 0x00, 0x00, 0x00, 0x00];
+        let trampoline = build_trampoline(&code, 0x10, 0xFFFF_F000).unwrap();
+        let jmp = disasm::decode(&trampoline.bytes, 0xFFFF_F000).unwrap();
+        assert_eq!(jmp.target(), Some(0x15));
+    }
+
+    #[test]
+    fn refuses_what_it_cant_move() {
+        // jne rel8
+        let code = [0x75, 0x05, 0x90, 0x90, 0x90, 0x90];
+        assert!(matches!(build_trampoline(&code, SOURCE, DESTINATION), Err(Error::UnsupportedRelative { .. })));
+
+        // je rel32 into the bytes that the jmp overwrites
+        let code = [0x0F, 0x84, 0xFD, 0xFF, 0xFF, 0xFF, 0x90];
+        assert!(matches!(
+            build_trampoline(&code, SOURCE, DESTINATION),
+            Err(Error::BranchIntoPatch { target, .. }) if target == SOURCE + 3
+        ));
+
+        // xor eax,eax; ret
+        let code = [0x31, 0xC0, 0xC3, INT3, INT3, INT3];
+        assert!(matches!(build_trampoline(&code, SOURCE, DESTINATION), Err(Error::TooShort { len: 3, .. })));
+
+        // The code ends in the middle of `mov eax,imm32`.
+        let code = [0x90, 0xB8, 0x01, 0x00];
+        assert!(matches!(
+            build_trampoline(&code, SOURCE, DESTINATION),
+            Err(Error::Decode(disasm::Error::Truncated(address))) if address == SOURCE + 1
+        ));
+
+        assert!(matches!(build_trampoline(&[], SOURCE, DESTINATION), Err(Error::Decode(_))));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn readable_len_stops_at_an_unreadable_page() {
+        use crate::memory::{self, ExecutableMemory};
+
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize };
+        let pages = ExecutableMemory::new(2 * page_size).unwrap();
+        let second = pages.address() + page_size;
+
+        unsafe {
+            assert_eq!(libc::mprotect(second as *mut _, page_size, libc::PROT_NONE), 0);
+        }
+
+        let near_the_end = (second - 3) as *const u8;
+        assert_eq!(memory::readable_len(near_the_end, 19), 3);
+        assert_eq!(memory::readable_len((second - 40) as *const u8, 19), 19);
+        assert_eq!(memory::readable_len(second as *const u8, 19), 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hooks_a_function() {
+        use super::InlineHook;
+        use crate::memory::ExecutableMemory;
+        use std::mem;
+
+        // mov eax,imm32; ret means the same in 32-bit and 64-bit code.
+        fn function(value: u8) -> ExecutableMemory {
+            let mut code = ExecutableMemory::new(64).unwrap();
+            code.as_mut_slice()[..6].copy_from_slice(&[0xB8, value, 0x00, 0x00, 0x00, 0xC3]);
+            code
+        }
+
+        fn call(address: usize) -> u32 {
+            unsafe { mem::transmute::<usize, extern "C" fn() -> u32>(address)() }
+        }
+
+        let target = function(1);
+        let detour = function(2);
+
+        unsafe {
+            let mut hook = InlineHook::new(target.address(), detour.address()).unwrap();
+            assert_eq!(hook.target(), target.address());
+            assert!(!hook.is_enabled());
+            assert_eq!(call(target.address()), 1);
+
+            hook.enable().unwrap();
+            assert!(hook.is_enabled());
+            assert_eq!(call(target.address()), 2);
+            assert_eq!(call(hook.trampoline()), 1);
+
+            hook.disable().unwrap();
+            assert!(!hook.is_enabled());
+            assert_eq!(call(target.address()), 1);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn refuses_to_hook_what_it_cant_read() {
+        use super::InlineHook;
+        use crate::memory::ExecutableMemory;
+
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize };
+        let pages = ExecutableMemory::new(2 * page_size).unwrap();
+        let second = pages.address() + page_size;
+
+        unsafe {
+            assert_eq!(libc::mprotect(second as *mut _, page_size, libc::PROT_NONE), 0);
+
+            let near_the_end = second - 3;
+            assert!(matches!(
+                InlineHook::new(near_the_end, pages.address()),
+                Err(Error::Unreadable(address)) if address == near_the_end
+            ));
+        }
+    }
+}
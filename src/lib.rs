@@ -1,12 +1,20 @@
 #![warn(clippy::pedantic)]
-#![allow(clippy::find_map)]
+#![allow(clippy::manual_find_map)]
+
+#[cfg(windows)]
 use std::io::{self, Read};
+#[cfg(windows)]
 use std::panic;
+#[cfg(windows)]
 use std::ptr;
 
+#[cfg(windows)]
 use log::{error, info};
+#[cfg(windows)]
 use simplelog::{Config, LevelFilter, TermLogger, TerminalMode};
+#[cfg(windows)]
 use wchar::wch_c as w;
+#[cfg(windows)]
 use winapi::{
     shared::minwindef::{BOOL, DWORD, HINSTANCE, LPVOID, TRUE},
     um::{
@@ -20,41 +28,88 @@ use winapi::{
     },
 };
 
+// Off Windows, we only build the parts that don't touch the game, and only for their tests.
+#[cfg(any(windows, test))]
+mod console;
+#[cfg(any(windows, test))]
+mod disasm;
+#[cfg(any(windows, test))]
+mod dispatch;
+#[cfg(windows)]
+mod engine;
+#[cfg(any(windows, test))]
+mod feature;
+#[cfg(windows)]
 mod game;
+#[cfg(any(windows, test))]
+mod guard;
+#[cfg(windows)]
 mod hook;
+#[cfg(any(windows, test))]
+mod inline_hook;
+#[cfg(windows)]
 mod macros;
+#[cfg(any(windows, test))]
 mod memory;
+#[cfg(any(windows, test))]
+mod message;
+#[cfg(windows)]
 mod module;
+#[cfg(any(windows, test))]
+mod offset_cache;
+#[cfg(any(windows, test))]
+mod offsets;
+#[cfg(any(windows, test))]
+mod pe;
+#[cfg(any(windows, test))]
+mod resolver;
+#[cfg(any(windows, test))]
+mod scanner;
+#[cfg(any(windows, test))]
+mod signature;
+#[cfg(windows)]
 mod single_thread_verifier;
+#[cfg(any(windows, test))]
+mod xref;
+#[cfg(any(windows, test))]
+mod variadic;
+#[cfg(any(windows, test))]
+mod vtable;
+#[cfg(windows)]
 mod yank;
 
+#[cfg(windows)]
 fn msg_box(text: &[u16], caption: &[u16]) {
     unsafe {
         MessageBoxW(ptr::null_mut(), text.as_ptr(), caption.as_ptr(), MB_OK);
     }
 }
 
+#[cfg(windows)]
 fn idle() {
     info!("Idling. Press enter to continue.");
     let mut sentinel = [0; 2];
     let _ = io::stdin().read_exact(&mut sentinel);
 }
 
+#[cfg(windows)]
 extern "system" fn on_attach(dll: LPVOID) -> DWORD {
     let result = panic::catch_unwind(|| {
         unsafe { AllocConsole() };
         println!("Allocated console.");
 
-        if let Err(e) = TermLogger::init(LevelFilter::Info, Config::default(), TerminalMode::Mixed) {
-            eprintln!("Failed to initialize logger: {}", e);
+        // The logger lets everything through, so that the console's `log` command can go up to trace.
+        if let Err(e) = TermLogger::init(LevelFilter::Trace, Config::default(), TerminalMode::Mixed) {
+            eprintln!("Failed to initialize logger: {e}");
             idle();
         } else {
+            log::set_max_level(LevelFilter::Info);
             info!("Initialized logger.");
             
             single_thread_verifier::notice();
 
             if let Err(e) = hook::run() {
-                error!("hook error: {}", e);
+                error!("hook error: {e}");
                 idle();
             }
             info!("Sleeping 1 second before detaching.");
@@ -75,6 +130,7 @@ extern "system" fn on_attach(dll: LPVOID) -> DWORD {
     0
 }
 
+#[cfg(windows)]
 #[no_mangle]
 #[allow(non_snake_case)]
 extern "system" fn DllMain(dll: HINSTANCE, reason: DWORD, _: LPVOID) -> BOOL {
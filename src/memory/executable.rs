@@ -0,0 +1,79 @@
+use super::Error;
+
+use std::slice;
+
+// Pages that we can write code into and then run, e.g. a trampoline. We free them on drop.
+pub struct ExecutableMemory {
+    address: *mut u8,
+    len: usize,
+}
+
+impl ExecutableMemory {
+    pub fn new(len: usize) -> Result<ExecutableMemory, Error> {
+        let address = unsafe { allocate(len) };
+
+        if address.is_null() {
+            #[allow(clippy::cast_sign_loss)]
+            let error_code = std::io::Error::last_os_error().raw_os_error().unwrap_or(0) as u32;
+            return Err(Error::Allocate { len, error_code });
+        }
+
+        Ok(ExecutableMemory { address, len })
+    }
+
+    pub fn address(&self) -> usize {
+        self.address as usize
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.address, self.len) }
+    }
+}
+
+impl Drop for ExecutableMemory {
+    fn drop(&mut self) {
+        unsafe {
+            free(self.address, self.len);
+        }
+    }
+}
+
+#[cfg(windows)]
+unsafe fn allocate(len: usize) -> *mut u8 {
+    use std::ptr;
+    use winapi::um::{memoryapi::VirtualAlloc, winnt::{MEM_COMMIT, MEM_RESERVE, PAGE_EXECUTE_READWRITE}};
+
+    VirtualAlloc(ptr::null_mut(), len, MEM_COMMIT | MEM_RESERVE, PAGE_EXECUTE_READWRITE).cast()
+}
+
+#[cfg(windows)]
+unsafe fn free(address: *mut u8, _len: usize) {
+    use winapi::um::{memoryapi::VirtualFree, winnt::MEM_RELEASE};
+
+    VirtualFree(address.cast(), 0, MEM_RELEASE);
+}
+
+#[cfg(unix)]
+unsafe fn allocate(len: usize) -> *mut u8 {
+    use std::ptr;
+
+    let address = libc::mmap(
+        ptr::null_mut(),
+        len,
+        libc::PROT_READ | libc::PROT_WRITE | libc::PROT_EXEC,
+        libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+        -1,
+        0,
+    );
+
+    if address == libc::MAP_FAILED {
+        ptr::null_mut()
+    } else {
+        address.cast()
+    }
+}
+
+#[cfg(unix)]
+unsafe fn free(address: *mut u8, len: usize) {
+    libc::munmap(address.cast(), len);
+}
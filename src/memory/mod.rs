@@ -0,0 +1,359 @@
+use std::marker::PhantomData;
+use std::mem;
+use std::ptr;
+use std::slice;
+
+use log::error;
+use thiserror::Error;
+
+mod executable;
+pub use executable::ExecutableMemory;
+
+mod protection;
+pub use protection::{MemoryProtection, Platform};
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("null pointer")]
+    NullPointer,
+
+    #[error("the pointer is unaligned; \
+             the pointer needs to be offset by {num_elements_needed_to_align_ptr} elements \
+             to align {address:#x} to the required alignment of {required_alignment} bytes")]
+    UnalignedPointer {
+        address: usize,
+        required_alignment: usize,
+        num_elements_needed_to_align_ptr: usize,
+    },
+
+    #[error("failed to change the page protection of {len} bytes at {address:#x}; OS error code {error_code:#x}")]
+    Protect {
+        address: usize,
+        len: usize,
+        error_code: u32,
+    },
+
+    #[error("failed to allocate {len} bytes of executable memory; OS error code {error_code:#x}")]
+    Allocate {
+        len: usize,
+        error_code: u32,
+    },
+
+    #[error("{} patched location(s) changed after we wrote them: {0:#x?}", .0.len())]
+    Conflicts(Vec<Conflict>),
+}
+
+// Someone else wrote to a location that we patched, so we left it alone instead of restoring it.
+#[derive(Debug)]
+pub struct Conflict {
+    pub address: usize,
+    pub expected: Vec<u8>,
+    pub found: Vec<u8>,
+}
+
+struct Staged {
+    address: *mut u8,
+    new: Vec<u8>,
+    old: Vec<u8>,
+
+    // Whether `new` is in memory, as opposed to `old`.
+    patched: bool,
+}
+
+impl Staged {
+    unsafe fn current(&self) -> &[u8] {
+        slice::from_raw_parts(self.address, self.new.len())
+    }
+
+    // A failed write may still have copied the bytes, e.g. when only putting back the protection failed.
+    unsafe fn write<P: MemoryProtection>(&mut self, patch: bool) -> Result<(), Error> {
+        let result = write::<P>(self.address, if patch { &self.new } else { &self.old });
+        self.patched = self.current() == self.new.as_slice() && (patch || self.new != self.old);
+        result
+    }
+}
+
+// A transaction of patches that we apply all-or-none and restore in reverse order.
+//
+//      let mut patches = PatchSet::new();
+//      patches.value(&mut (*table).function, Some(my_function))?;
+//      patches.apply()?;
+//
+// `P` changes page protection around each write. Only tests need anything other than the default.
+pub struct PatchSet<P: MemoryProtection = Platform> {
+    staged: Vec<Staged>,
+    applied: bool,
+    protection: PhantomData<P>,
+}
+
+impl PatchSet {
+    pub fn new() -> PatchSet {
+        PatchSet::with_protection()
+    }
+}
+
+impl Default for PatchSet {
+    fn default() -> PatchSet {
+        PatchSet::new()
+    }
+}
+
+impl<P: MemoryProtection> PatchSet<P> {
+    pub fn with_protection() -> PatchSet<P> {
+        PatchSet {
+            staged: vec![],
+            applied: false,
+            protection: PhantomData,
+        }
+    }
+
+    pub unsafe fn bytes(&mut self, address: *mut u8, bytes: &[u8]) -> Result<&mut PatchSet<P>, Error> {
+        ptr_check(address)?;
+
+        self.staged.push(Staged {
+            address,
+            new: bytes.to_vec(),
+            old: vec![],
+            patched: false,
+        });
+
+        Ok(self)
+    }
+
+    pub unsafe fn value<T: Copy>(&mut self, address: *mut T, value: T) -> Result<&mut PatchSet<P>, Error> {
+        ptr_check(address)?;
+        self.bytes(address.cast(), as_bytes(&value))
+    }
+
+    // Writes every staged patch. If one write fails, we undo the writes before it, and the failed write itself.
+    pub unsafe fn apply(&mut self) -> Result<(), Error> {
+        if self.applied {
+            return Ok(());
+        }
+
+        for index in 0..self.staged.len() {
+            let staged = &mut self.staged[index];
+            staged.old = staged.current().to_vec();
+
+            if let Err(e) = staged.write::<P>(true) {
+                for undo in self.staged[..=index].iter_mut().rev().filter(|staged| staged.patched) {
+                    let _ = undo.write::<P>(false);
+                }
+
+                return Err(e);
+            }
+        }
+
+        self.applied = true;
+        Ok(())
+    }
+
+    // Restores every patch in reverse order.
+    // A location that no longer holds what we wrote is a conflict, and we leave it alone.
+    // We stay applied until every patch is restored, so that a later `restore` can try the rest again.
+    pub unsafe fn restore(&mut self) -> Result<(), Error> {
+        if !self.applied {
+            return Ok(());
+        }
+
+        let mut conflicts = vec![];
+        let mut first_error = None;
+
+        for staged in self.staged.iter_mut().rev().filter(|staged| staged.patched) {
+            let current = staged.current();
+
+            if current != staged.new.as_slice() {
+                conflicts.push(Conflict {
+                    address: staged.address as usize,
+                    expected: staged.new.clone(),
+                    found: current.to_vec(),
+                });
+
+                continue;
+            }
+
+            if let Err(e) = staged.write::<P>(false) {
+                if first_error.is_none() {
+                    first_error = Some(e);
+                }
+            }
+        }
+
+        self.applied = self.staged.iter().any(|staged| staged.patched);
+
+        if let Some(e) = first_error {
+            Err(e)
+        } else if conflicts.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Conflicts(conflicts))
+        }
+    }
+
+    pub fn is_applied(&self) -> bool {
+        self.applied
+    }
+}
+
+impl<P: MemoryProtection> Drop for PatchSet<P> {
+    fn drop(&mut self) {
+        if let Err(e) = unsafe { self.restore() } {
+            error!("Failed to restore a patch set: {e}");
+        }
+    }
+}
+
+// Briefly changes page protection to write `bytes` at `address`.
+pub unsafe fn write<P: MemoryProtection>(address: *mut u8, bytes: &[u8]) -> Result<(), Error> {
+    let saved = P::unprotect(address, bytes.len())?;
+    ptr::copy_nonoverlapping(bytes.as_ptr(), address, bytes.len());
+    P::protect(address, bytes.len(), saved)?;
+    P::flush_instruction_cache(address, bytes.len());
+    Ok(())
+}
+
+fn as_bytes<T: Copy>(value: &T) -> &[u8] {
+    unsafe { slice::from_raw_parts(ptr::from_ref(value).cast(), mem::size_of::<T>()) }
+}
+
+// How many of the `max` bytes at `address` we can read.
+pub fn readable_len(address: *const u8, max: usize) -> usize {
+    Platform::readable_len(address, max)
+}
+
+pub fn ptr_check<T>(p: *const T) -> Result<(), Error> {
+    if p.is_null() {
+        return Err(Error::NullPointer);
+    }
+
+    let required_alignment = mem::align_of::<T>();
+    let num_elements_needed_to_align_ptr = p.align_offset(required_alignment);
+
+    if num_elements_needed_to_align_ptr > 0 {
+        return Err(Error::UnalignedPointer {
+            address: p as usize,
+            required_alignment,
+            num_elements_needed_to_align_ptr,
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Error, MemoryProtection, PatchSet};
+    use std::cell::Cell;
+
+    thread_local! {
+        // How many more times `Flaky::protect` succeeds before it fails.
+        static PROTECTS_LEFT: Cell<usize> = const { Cell::new(usize::MAX) };
+    }
+
+    // Writes straight to ordinary memory, and fails to put back the protection when told to.
+    struct Flaky;
+
+    impl MemoryProtection for Flaky {
+        type Saved = ();
+
+        unsafe fn unprotect(_address: *mut u8, _len: usize) -> Result<(), Error> {
+            Ok(())
+        }
+
+        unsafe fn protect(address: *mut u8, len: usize, _saved: ()) -> Result<(), Error> {
+            let left = PROTECTS_LEFT.with(Cell::get);
+
+            if left == 0 {
+                return Err(Error::Protect { address: address as usize, len, error_code: 5 });
+            }
+
+            PROTECTS_LEFT.with(|cell| cell.set(left - 1));
+            Ok(())
+        }
+
+        fn readable_len(_address: *const u8, max: usize) -> usize {
+            max
+        }
+    }
+
+    #[test]
+    fn apply_undoes_the_failed_write() {
+        let mut first = [1_u8; 4];
+        let mut second = [2_u8; 4];
+        let mut patches = PatchSet::<Flaky>::with_protection();
+
+        unsafe {
+            patches.bytes(first.as_mut_ptr(), &[0xAA; 4]).unwrap();
+            patches.bytes(second.as_mut_ptr(), &[0xBB; 4]).unwrap();
+
+            // The second write copies its bytes and then fails to protect.
+            PROTECTS_LEFT.with(|cell| cell.set(1));
+            assert!(matches!(patches.apply(), Err(Error::Protect { .. })));
+            PROTECTS_LEFT.with(|cell| cell.set(usize::MAX));
+        }
+
+        assert_eq!(first, [1; 4]);
+        assert_eq!(second, [2; 4]);
+        assert!(!patches.is_applied());
+    }
+
+    #[test]
+    fn restore_stays_applied_with_conflicts() {
+        let mut first = [1_u8; 4];
+        let mut second = [2_u8; 4];
+        let mut patches = PatchSet::<Flaky>::with_protection();
+
+        unsafe {
+            patches.bytes(first.as_mut_ptr(), &[0xAA; 4]).unwrap();
+            patches.bytes(second.as_mut_ptr(), &[0xBB; 4]).unwrap();
+            patches.apply().unwrap();
+
+            // Someone else patches over us.
+            second.as_mut_ptr().write(0xCC);
+
+            match patches.restore() {
+                Err(Error::Conflicts(conflicts)) => {
+                    assert_eq!(conflicts.len(), 1);
+                    assert_eq!(conflicts[0].address, second.as_ptr() as usize);
+                    assert_eq!(conflicts[0].expected, [0xBB; 4]);
+                    assert_eq!(conflicts[0].found, [0xCC, 0xBB, 0xBB, 0xBB]);
+                }
+
+                result => panic!("expected a conflict, got {:?}", result),
+            }
+
+            assert_eq!(first, [1; 4]);
+            assert!(patches.is_applied());
+
+            // Once they undo theirs, we can finish.
+            second.as_mut_ptr().write(0xBB);
+            patches.restore().unwrap();
+        }
+
+        assert_eq!(second, [2; 4]);
+        assert!(!patches.is_applied());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn patches_read_only_page() {
+        use super::ExecutableMemory;
+
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize };
+        let page = ExecutableMemory::new(page_size).unwrap();
+        let address = page.address();
+
+        unsafe {
+            assert_eq!(libc::mprotect(address as *mut _, page_size, libc::PROT_READ), 0);
+
+            let mut patches = PatchSet::new();
+            patches.value(address as *mut u32, 0xDEAD_BEEF_u32).unwrap();
+            patches.apply().unwrap();
+            assert_eq!(*(address as *const u32), 0xDEAD_BEEF);
+
+            patches.restore().unwrap();
+            assert_eq!(*(address as *const u32), 0);
+        }
+    }
+}
@@ -0,0 +1,290 @@
+use super::Error;
+
+// Changes page protection so that we can write to code and read-only data.
+// Windows is the backend the hook uses. The Linux backend lets us exercise the same patching code off Windows.
+pub trait MemoryProtection {
+    // Whatever we need to put the old protection back.
+    type Saved;
+
+    // Makes the pages that cover `len` bytes at `address` readable, writable, and executable.
+    unsafe fn unprotect(address: *mut u8, len: usize) -> Result<Self::Saved, Error>;
+
+    // Puts back the protection that `unprotect` replaced.
+    unsafe fn protect(address: *mut u8, len: usize, saved: Self::Saved) -> Result<(), Error>;
+
+    // Makes the CPU see code that we wrote.
+    unsafe fn flush_instruction_cache(_address: *const u8, _len: usize) {}
+
+    // How many of the `max` bytes at `address` we can read before we hit a page that we can't.
+    fn readable_len(address: *const u8, max: usize) -> usize;
+}
+
+#[cfg(windows)]
+pub type Platform = Windows;
+
+#[cfg(unix)]
+pub type Platform = Linux;
+
+#[cfg(windows)]
+type Protection = u32;
+
+#[cfg(unix)]
+type Protection = i32;
+
+// A run of pages that had one protection before we unprotected them.
+pub struct Region {
+    start: usize,
+    end: usize,
+    protection: Protection,
+}
+
+#[cfg(windows)]
+pub struct Windows;
+
+#[cfg(windows)]
+impl MemoryProtection for Windows {
+    type Saved = Vec<Region>;
+
+    // VirtualProtect only reports the old protection of the first page, so we go region by region.
+    unsafe fn unprotect(address: *mut u8, len: usize) -> Result<Vec<Region>, Error> {
+        use std::mem::{self, MaybeUninit};
+        use winapi::um::memoryapi::{VirtualProtect, VirtualQuery};
+        use winapi::um::winnt::{MEMORY_BASIC_INFORMATION, PAGE_EXECUTE_READWRITE};
+
+        let end = address as usize + len;
+        let mut regions = vec![];
+        let mut cursor = address as usize;
+
+        while cursor < end {
+            let mut info = MaybeUninit::<MEMORY_BASIC_INFORMATION>::uninit();
+            let queried = VirtualQuery(cursor as *const _, info.as_mut_ptr(), mem::size_of_val(&info)) != 0;
+
+            let region_end = if queried {
+                let info = info.assume_init();
+                (info.BaseAddress as usize + info.RegionSize).min(end)
+            } else {
+                end
+            };
+
+            let mut old_protection = 0;
+
+            if !queried || VirtualProtect(cursor as *mut _, region_end - cursor, PAGE_EXECUTE_READWRITE, &mut old_protection) == 0 {
+                // Put back the regions that we already changed.
+                let error = protect_error(address, len);
+                let _ = Windows::protect(address, len, regions);
+                return Err(error);
+            }
+
+            regions.push(Region { start: cursor, end: region_end, protection: old_protection });
+            cursor = region_end;
+        }
+
+        Ok(regions)
+    }
+
+    unsafe fn protect(address: *mut u8, len: usize, saved: Vec<Region>) -> Result<(), Error> {
+        use winapi::um::memoryapi::VirtualProtect;
+
+        let mut result = Ok(());
+
+        for region in saved {
+            let mut old_protection = 0;
+
+            if VirtualProtect(region.start as *mut _, region.end - region.start, region.protection, &mut old_protection) == 0
+                && result.is_ok() {
+
+                result = Err(protect_error(address, len));
+            }
+        }
+
+        result
+    }
+
+    unsafe fn flush_instruction_cache(address: *const u8, len: usize) {
+        use winapi::um::processthreadsapi::{FlushInstructionCache, GetCurrentProcess};
+
+        FlushInstructionCache(GetCurrentProcess(), address.cast(), len);
+    }
+
+    fn readable_len(address: *const u8, max: usize) -> usize {
+        use std::mem::{self, MaybeUninit};
+        use winapi::um::memoryapi::VirtualQuery;
+        use winapi::um::winnt::{MEMORY_BASIC_INFORMATION, MEM_COMMIT, PAGE_GUARD, PAGE_NOACCESS};
+
+        let start = address as usize;
+        let end = start.saturating_add(max);
+        let mut cursor = start;
+
+        while cursor < end {
+            let mut info = MaybeUninit::<MEMORY_BASIC_INFORMATION>::uninit();
+
+            let info = unsafe {
+                if VirtualQuery(cursor as *const _, info.as_mut_ptr(), mem::size_of_val(&info)) == 0 {
+                    break;
+                }
+
+                info.assume_init()
+            };
+
+            if info.State != MEM_COMMIT || info.Protect & (PAGE_NOACCESS | PAGE_GUARD) != 0 || info.Protect == 0 {
+                break;
+            }
+
+            cursor = info.BaseAddress as usize + info.RegionSize;
+        }
+
+        cursor.min(end) - start
+    }
+}
+
+#[cfg(unix)]
+pub struct Linux;
+
+#[cfg(unix)]
+impl Linux {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn page_size() -> usize {
+        unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+    }
+
+    // mprotect can't tell us the old protection, so we read it from /proc/self/maps.
+    fn regions(start: usize, end: usize) -> Result<Vec<Region>, Error> {
+        let maps = std::fs::read_to_string("/proc/self/maps").map_err(|e| Error::Protect {
+            address: start,
+            len: end - start,
+            #[allow(clippy::cast_sign_loss)]
+            error_code: e.raw_os_error().unwrap_or(0) as u32,
+        })?;
+
+        let regions = maps
+            .lines()
+            .filter_map(|line| {
+                // 55d0c8a4e000-55d0c8a50000 r-xp 00002000 08:01 1234 /usr/bin/cat
+                let mut fields = line.split_whitespace();
+                let mut range = fields.next()?.split('-');
+                let low = usize::from_str_radix(range.next()?, 16).ok()?;
+                let high = usize::from_str_radix(range.next()?, 16).ok()?;
+                let permissions = fields.next()?.as_bytes();
+
+                let mut protection = libc::PROT_NONE;
+
+                for (flag, permission) in &[(b'r', libc::PROT_READ), (b'w', libc::PROT_WRITE), (b'x', libc::PROT_EXEC)] {
+                    if permissions.contains(flag) {
+                        protection |= permission;
+                    }
+                }
+
+                Some(Region { start: low.max(start), end: high.min(end), protection })
+            })
+            .filter(|region| region.start < region.end)
+            .collect();
+
+        Ok(regions)
+    }
+}
+
+#[cfg(unix)]
+impl MemoryProtection for Linux {
+    type Saved = Vec<Region>;
+
+    unsafe fn unprotect(address: *mut u8, len: usize) -> Result<Vec<Region>, Error> {
+        let page_size = Linux::page_size();
+        let start = address as usize / page_size * page_size;
+        let end = (address as usize + len).div_ceil(page_size) * page_size;
+
+        let regions = Linux::regions(start, end)?;
+
+        if libc::mprotect(start as *mut _, end - start, libc::PROT_READ | libc::PROT_WRITE | libc::PROT_EXEC) != 0 {
+            return Err(protect_error(address, len));
+        }
+
+        Ok(regions)
+    }
+
+    unsafe fn protect(address: *mut u8, len: usize, saved: Vec<Region>) -> Result<(), Error> {
+        for region in saved {
+            if libc::mprotect(region.start as *mut _, region.end - region.start, region.protection) != 0 {
+                return Err(protect_error(address, len));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn readable_len(address: *const u8, max: usize) -> usize {
+        let start = address as usize;
+        let end = start.saturating_add(max);
+        let mut cursor = start;
+
+        for region in Linux::regions(start, end).unwrap_or_default() {
+            if region.start != cursor || region.protection & libc::PROT_READ == 0 {
+                break;
+            }
+
+            cursor = region.end;
+        }
+
+        cursor - start
+    }
+}
+
+fn protect_error(address: *mut u8, len: usize) -> Error {
+    #[allow(clippy::cast_sign_loss)]
+    let error_code = std::io::Error::last_os_error().raw_os_error().unwrap_or(0) as u32;
+
+    Error::Protect {
+        address: address as usize,
+        len,
+        error_code,
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::{Linux, MemoryProtection};
+    use crate::memory::{self, ExecutableMemory};
+
+    fn protection_of(address: usize) -> i32 {
+        Linux::regions(address, address + 1).unwrap()[0].protection
+    }
+
+    #[test]
+    fn writes_through_read_only_page() {
+        let page_size = Linux::page_size();
+        let mut page = ExecutableMemory::new(page_size).unwrap();
+        let address = page.address();
+        page.as_mut_slice()[..4].copy_from_slice(&[1, 2, 3, 4]);
+
+        unsafe {
+            assert_eq!(libc::mprotect(address as *mut _, page_size, libc::PROT_READ), 0);
+            memory::write::<Linux>((address + 1) as *mut u8, &[0xAA, 0xBB]).unwrap();
+        }
+
+        assert_eq!(page.as_mut_slice()[..4], [1, 0xAA, 0xBB, 4]);
+        assert_eq!(protection_of(address), libc::PROT_READ);
+    }
+
+    #[test]
+    fn restores_each_region() {
+        let page_size = Linux::page_size();
+        let pages = ExecutableMemory::new(2 * page_size).unwrap();
+        let first = pages.address();
+        let second = first + page_size;
+
+        unsafe {
+            assert_eq!(libc::mprotect(first as *mut _, page_size, libc::PROT_READ), 0);
+            assert_eq!(libc::mprotect(second as *mut _, page_size, libc::PROT_READ | libc::PROT_EXEC), 0);
+
+            // Straddle the boundary between the pages.
+            let address = (second - 2) as *mut u8;
+            let saved = Linux::unprotect(address, 4).unwrap();
+            assert_eq!(saved.len(), 2);
+            assert_eq!(protection_of(first), libc::PROT_READ | libc::PROT_WRITE | libc::PROT_EXEC);
+
+            Linux::protect(address, 4, saved).unwrap();
+        }
+
+        assert_eq!(protection_of(first), libc::PROT_READ);
+        assert_eq!(protection_of(second), libc::PROT_READ | libc::PROT_EXEC);
+    }
+}
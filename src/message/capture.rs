@@ -0,0 +1,240 @@
+// A recording of user messages, so that we can replay a play session through the decoders offline.
+// Everything is little-endian.
+//
+//      header:  b"SCHC", version: u16
+//      record:  time: f64, name_len: u8, name, id: i32, len: u16, payload
+//
+// `time` is seconds since the capture started, and `id` is the iMsg that the server assigned.
+
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
+
+use bstr::BString;
+use thiserror::Error;
+
+const MAGIC: [u8; 4] = *b"SCHC";
+const VERSION: u16 = 1;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+
+    #[error("this isn't a capture; it starts with {0:02x?}")]
+    Magic([u8; 4]),
+
+    #[error("the capture is version {0}, but we read version {}", VERSION)]
+    Version(u16),
+
+    #[error("the capture ends in the middle of a record")]
+    Truncated,
+
+    #[error("a {what} of {len} bytes is too long for a capture")]
+    TooLong {
+        what: &'static str,
+        len: usize,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Record {
+    pub time: f64,
+    pub name: BString,
+    pub id: i32,
+    pub bytes: Vec<u8>,
+}
+
+pub struct CaptureWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> CaptureWriter<W> {
+    pub fn new(mut writer: W) -> Result<CaptureWriter<W>, Error> {
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        Ok(CaptureWriter { writer })
+    }
+
+    pub fn write(&mut self, record: &Record) -> Result<(), Error> {
+        let name_len = u8::try_from(record.name.len())
+            .map_err(|_| Error::TooLong { what: "name", len: record.name.len() })?;
+
+        let len = u16::try_from(record.bytes.len())
+            .map_err(|_| Error::TooLong { what: "payload", len: record.bytes.len() })?;
+
+        self.writer.write_all(&record.time.to_le_bytes())?;
+        self.writer.write_all(&[name_len])?;
+        self.writer.write_all(&record.name)?;
+        self.writer.write_all(&record.id.to_le_bytes())?;
+        self.writer.write_all(&len.to_le_bytes())?;
+        self.writer.write_all(&record.bytes)?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), Error> {
+        Ok(self.writer.flush()?)
+    }
+}
+
+// The records of a capture, in the order we recorded them.
+//
+//      for record in CaptureReader::new(File::open(path)?)? {
+//          let record = record?;
+//          println!("{:?}", message::decode(&record.name, &record.bytes));
+//      }
+pub struct CaptureReader<R: Read> {
+    reader: R,
+}
+
+impl<R: Read> CaptureReader<R> {
+    pub fn new(mut reader: R) -> Result<CaptureReader<R>, Error> {
+        let magic: [u8; 4] = read_array(&mut reader)?.ok_or(Error::Truncated)?;
+
+        if magic != MAGIC {
+            return Err(Error::Magic(magic));
+        }
+
+        let version = u16::from_le_bytes(read_array(&mut reader)?.ok_or(Error::Truncated)?);
+
+        if version != VERSION {
+            return Err(Error::Version(version));
+        }
+
+        Ok(CaptureReader { reader })
+    }
+
+    // None at a clean end of the capture.
+    fn read_record(&mut self) -> Result<Option<Record>, Error> {
+        let time = match read_array(&mut self.reader)? {
+            Some(time) => f64::from_le_bytes(time),
+            None => return Ok(None),
+        };
+
+        let [name_len] = self.read_array()?;
+        let name = self.read_vec(name_len.into())?;
+        let id = i32::from_le_bytes(self.read_array()?);
+        let len = u16::from_le_bytes(self.read_array()?);
+        let bytes = self.read_vec(len.into())?;
+
+        Ok(Some(Record {
+            time,
+            name: name.into(),
+            id,
+            bytes,
+        }))
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        read_array(&mut self.reader)?.ok_or(Error::Truncated)
+    }
+
+    fn read_vec(&mut self, len: usize) -> Result<Vec<u8>, Error> {
+        let mut bytes = vec![0; len];
+        self.reader.read_exact(&mut bytes).map_err(truncated)?;
+        Ok(bytes)
+    }
+}
+
+impl<R: Read> Iterator for CaptureReader<R> {
+    type Item = Result<Record, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+// None if the reader is already at its end. An end after the first byte is a truncation.
+fn read_array<const N: usize>(reader: &mut impl Read) -> Result<Option<[u8; N]>, Error> {
+    let mut array = [0; N];
+    let mut filled = 0;
+
+    while filled < N {
+        match reader.read(&mut array[filled..]) {
+            Ok(0) if filled == 0 => return Ok(None),
+            Ok(0) => return Err(Error::Truncated),
+            Ok(read) => filled += read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Ok(Some(array))
+}
+
+fn truncated(e: io::Error) -> Error {
+    if e.kind() == io::ErrorKind::UnexpectedEof {
+        Error::Truncated
+    } else {
+        e.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CaptureReader, CaptureWriter, Error, Record};
+
+    fn records() -> Vec<Record> {
+        vec![
+            Record { time: 0.0, name: "Health".into(), id: 71, bytes: vec![100] },
+            Record { time: 0.25, name: "InitHUD".into(), id: 64, bytes: vec![] },
+            Record { time: 1.5, name: "TextMsg".into(), id: 77, bytes: b"\x04#Game_will_restart_in\x0010\x00".to_vec() },
+        ]
+    }
+
+    fn capture(records: &[Record]) -> Vec<u8> {
+        let mut writer = CaptureWriter::new(vec![]).unwrap();
+
+        for record in records {
+            writer.write(record).unwrap();
+        }
+
+        writer.writer
+    }
+
+    fn read(bytes: &[u8]) -> Result<Vec<Record>, Error> {
+        CaptureReader::new(bytes)?.collect()
+    }
+
+    #[test]
+    fn records_round_trip() {
+        assert_eq!(read(&capture(&records())).unwrap(), records());
+        assert_eq!(read(&capture(&[])).unwrap(), []);
+    }
+
+    // Cutting the capture anywhere but between two records is a truncation.
+    #[test]
+    fn cut_captures_are_truncated() {
+        let records = records();
+        let bytes = capture(&records);
+
+        // Where the header and each record end.
+        let ends = (0..=records.len()).map(|count| capture(&records[..count]).len()).collect::<Vec<_>>();
+
+        for len in 0..bytes.len() {
+            match read(&bytes[..len]) {
+                Ok(read) => assert!(ends.contains(&len), "{}: {:?}", len, read),
+                Err(Error::Truncated) => assert!(!ends.contains(&len), "{}", len),
+                Err(e) => panic!("{}: {}", len, e),
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(matches!(read(b"PK\x03\x04\x01\x00"), Err(Error::Magic(magic)) if magic == *b"PK\x03\x04"));
+        assert!(matches!(read(b"SCHC\x02\x00"), Err(Error::Version(2))));
+    }
+
+    #[test]
+    fn rejects_long_records() {
+        let mut writer = CaptureWriter::new(vec![]).unwrap();
+
+        let name = Record { time: 0.0, name: vec![b'a'; 256].into(), id: 0, bytes: vec![] };
+        assert!(matches!(writer.write(&name), Err(Error::TooLong { what: "name", len: 256 })));
+
+        let payload = Record { time: 0.0, name: "Health".into(), id: 0, bytes: vec![0; 0x1_0000] };
+        assert!(matches!(writer.write(&payload), Err(Error::TooLong { what: "payload", len: 0x1_0000 })));
+
+        assert_eq!(writer.writer.len(), 6);
+    }
+}
@@ -0,0 +1,287 @@
+// One struct for each user message that we know the layout of, in the layouts of the HL SDK's HUD code.
+// Sven Co-op grew out of that code, but it sends more than the SDK in some messages, e.g. ScoreInfo.
+// Each struct keeps the bytes after its fields in `extra`, so a longer message still decodes and encodes back.
+// A shorter message is a decode error.
+//
+//      match message::decode(name, bytes)? {
+//          Message::Health(Health { value, .. }) => info!("{value} health"),
+//          _ => {}
+//      }
+
+use super::{MsgReader, MsgWriter};
+
+use bstr::{BStr, BString, ByteSlice};
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum Error {
+    #[error("{name}: {source}")]
+    Read {
+        name: &'static str,
+        source: super::Error,
+    },
+}
+
+// The Rust type, read, and write of each encoding.
+// `strings` is every string until the end of the message.
+
+macro_rules! field_type {
+    (byte) => { u8 };
+    (char) => { i8 };
+    (short) => { i16 };
+    (word) => { u16 };
+    (long) => { i32 };
+    (float) => { f32 };
+    (coord) => { f32 };
+    (angle) => { f32 };
+    (hires_angle) => { f32 };
+    (string) => { BString };
+    (strings) => { Vec<BString> };
+}
+
+macro_rules! read_field {
+    ($reader:ident, byte) => { $reader.read_byte() };
+    ($reader:ident, char) => { $reader.read_char() };
+    ($reader:ident, short) => { $reader.read_short() };
+    ($reader:ident, word) => { $reader.read_word() };
+    ($reader:ident, long) => { $reader.read_long() };
+    ($reader:ident, float) => { $reader.read_float() };
+    ($reader:ident, coord) => { $reader.read_coord() };
+    ($reader:ident, angle) => { $reader.read_angle() };
+    ($reader:ident, hires_angle) => { $reader.read_hires_angle() };
+    ($reader:ident, string) => { $reader.read_string().map(BString::from) };
+    ($reader:ident, strings) => { read_strings(&mut $reader) };
+}
+
+macro_rules! write_field {
+    ($writer:ident, byte, $value:expr) => { $writer.write_byte(*$value) };
+    ($writer:ident, char, $value:expr) => { $writer.write_char(*$value) };
+    ($writer:ident, short, $value:expr) => { $writer.write_short(*$value) };
+    ($writer:ident, word, $value:expr) => { $writer.write_word(*$value) };
+    ($writer:ident, long, $value:expr) => { $writer.write_long(*$value) };
+    ($writer:ident, float, $value:expr) => { $writer.write_float(*$value) };
+    ($writer:ident, coord, $value:expr) => { $writer.write_coord(*$value) };
+    ($writer:ident, angle, $value:expr) => { $writer.write_angle(*$value) };
+    ($writer:ident, hires_angle, $value:expr) => { $writer.write_hires_angle(*$value) };
+    ($writer:ident, string, $value:expr) => { $writer.write_string($value) };
+    ($writer:ident, strings, $value:expr) => { write_strings(&mut $writer, $value) };
+}
+
+macro_rules! user_msgs {
+    ($($name:ident { $($field:ident: $kind:ident),* $(,)? })*) => {
+        // The names come from the game, so some are all caps.
+        $(
+            #[allow(clippy::upper_case_acronyms)]
+            #[derive(Clone, Debug, PartialEq)]
+            pub struct $name {
+                $(pub $field: field_type!($kind),)*
+
+                // The bytes after the fields that we know.
+                pub extra: Vec<u8>,
+            }
+        )*
+
+        #[allow(clippy::upper_case_acronyms)]
+        #[derive(Clone, Debug, PartialEq)]
+        pub enum Message {
+            $($name($name),)*
+            Raw(Raw),
+        }
+
+        impl Message {
+            pub fn name(&self) -> &BStr {
+                match self {
+                    $(Message::$name(_) => stringify!($name).into(),)*
+                    Message::Raw(raw) => raw.name.as_bstr(),
+                }
+            }
+
+            // The bytes that decode back into this message.
+            pub fn encode(&self) -> Result<Vec<u8>, super::Error> {
+                let mut writer = MsgWriter::new();
+
+                match self {
+                    $(
+                        Message::$name(message) => {
+                            $(write_field!(writer, $kind, &message.$field)?;)*
+                            writer.write_bytes(&message.extra)?;
+                        }
+                    )*
+
+                    Message::Raw(raw) => {
+                        writer.write_bytes(&raw.bytes)?;
+                    }
+                }
+
+                Ok(writer.into_bytes())
+            }
+        }
+
+        // Decodes the message that the game registered as `name`. An unknown name decodes to Raw.
+        pub fn decode(name: &[u8], bytes: &[u8]) -> Result<Message, Error> {
+            let mut reader = MsgReader::new(bytes);
+
+            $(
+                if name == stringify!($name).as_bytes() {
+                    #[allow(unused_variables)]
+                    let read = |error| Error::Read { name: stringify!($name), source: error };
+
+                    return Ok(Message::$name($name {
+                        $($field: read_field!(reader, $kind).map_err(read)?,)*
+                        extra: reader.read_rest().to_vec(),
+                    }));
+                }
+            )*
+
+            Ok(Message::Raw(Raw {
+                name: BString::from(name),
+                bytes: bytes.to_vec(),
+            }))
+        }
+    };
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Raw {
+    pub name: BString,
+    pub bytes: Vec<u8>,
+}
+
+fn read_strings(reader: &mut MsgReader) -> Result<Vec<BString>, super::Error> {
+    let mut strings = vec![];
+
+    while !reader.is_empty() {
+        strings.push(reader.read_string()?.into());
+    }
+
+    Ok(strings)
+}
+
+fn write_strings<'w>(writer: &'w mut MsgWriter, strings: &[BString]) -> Result<&'w mut MsgWriter, super::Error> {
+    for string in strings {
+        writer.write_string(string)?;
+    }
+
+    Ok(writer)
+}
+
+user_msgs! {
+    AllowSpec { allowed: byte }
+    AmmoPickup { index: byte, count: byte }
+    AmmoX { index: byte, count: byte }
+    Battery { armor: short }
+    Concuss { amount: byte }
+    CurWeapon { state: byte, id: char, clip: char }
+    Damage { armor: byte, taken: byte, bits: long, origin_x: coord, origin_y: coord, origin_z: coord }
+    DeathMsg { killer: byte, victim: byte, weapon: string }
+    FlashBat { battery: byte }
+    Flashlight { on: byte, battery: byte }
+    GameTitle { show: byte }
+    Geiger { range: byte }
+    Health { value: byte }
+    HideHUD { flags: byte }
+    InitHUD {}
+    ItemPickup { name: string }
+    MOTD { last: byte, text: string }
+    ReqState {}
+    ResetHUD { unused: byte }
+    SayText { client: byte, text: string }
+    ScoreInfo { client: byte, frags: short, deaths: short, player_class: short, team: short }
+    ScreenFade { duration: word, hold_time: word, flags: short, r: byte, g: byte, b: byte, a: byte }
+    ScreenShake { amplitude: word, duration: word, frequency: word }
+    ServerName { name: string }
+    SetFOV { fov: byte }
+    ShowMenu { valid_slots: short, display_time: char, need_more: byte, text: string }
+    Spectator { client: byte, on: byte }
+    TeamInfo { client: byte, team: string }
+    TeamNames { count: byte, names: strings }
+    TeamScore { team: string, frags: short, deaths: short }
+    TextMsg { destination: byte, text: string, args: strings }
+    Train { position: byte }
+    ViewMode {}
+    VoiceMask { audible_players: long, server_banned_players: long }
+    WeapPickup { id: byte }
+    WeaponList { name: string, ammo1: char, max1: byte, ammo2: char, max2: byte, slot: char, position: char, id: char, flags: byte }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, Error, Health, Message, Raw, ScoreInfo, ScreenFade, TextMsg};
+    use crate::message;
+
+    // Provisional: we built these by hand from the SDK's layouts, because we have no capture from the game yet.
+    // They only show that each decoder and its encoder agree, not that the game sends these layouts.
+    // Swap in captured ScoreInfo, TextMsg, and WeaponList payloads once we record a session.
+    const PROVISIONAL_PAYLOADS: &[(&str, &[u8])] = &[
+        ("Health", &[100]),
+        ("ScoreInfo", &[3, 12, 0, 0xFF, 0xFF, 0, 0, 2, 0]),
+        ("ScreenFade", &[0x00, 0x10, 0x00, 0x08, 0x01, 0x00, 255, 0, 0, 128]),
+        ("TextMsg", b"\x04#Game_will_restart_in\x0010\x00SECOND\x00"),
+        ("TeamNames", b"\x02blue\x00red\x00"),
+        ("InitHUD", &[]),
+        ("VoteMenu", &[1, 2, 3]),
+    ];
+
+    #[test]
+    fn provisional_payloads_round_trip() {
+        for (name, bytes) in PROVISIONAL_PAYLOADS {
+            let message = decode(name.as_bytes(), bytes).unwrap_or_else(|e| panic!("{}: {}", name, e));
+            assert_eq!(message.name(), name);
+            assert_eq!(message.encode().unwrap(), *bytes, "{name}");
+        }
+    }
+
+    #[test]
+    fn decodes_the_fields() {
+        assert_eq!(decode(b"Health", &[100]), Ok(Message::Health(Health { value: 100, extra: vec![] })));
+
+        assert_eq!(
+            decode(b"ScoreInfo", PROVISIONAL_PAYLOADS[1].1),
+            Ok(Message::ScoreInfo(ScoreInfo { client: 3, frags: 12, deaths: -1, player_class: 0, team: 2, extra: vec![] })),
+        );
+
+        assert_eq!(
+            decode(b"ScreenFade", PROVISIONAL_PAYLOADS[2].1),
+            Ok(Message::ScreenFade(ScreenFade {
+                duration: 0x1000, hold_time: 0x0800, flags: 1, r: 255, g: 0, b: 0, a: 128, extra: vec![],
+            })),
+        );
+
+        assert_eq!(
+            decode(b"TextMsg", PROVISIONAL_PAYLOADS[3].1),
+            Ok(Message::TextMsg(TextMsg {
+                destination: 4,
+                text: "#Game_will_restart_in".into(),
+                args: vec!["10".into(), "SECOND".into()],
+                extra: vec![],
+            })),
+        );
+
+        assert_eq!(decode(b"VoteMenu", &[1]), Ok(Message::Raw(Raw { name: "VoteMenu".into(), bytes: vec![1] })));
+    }
+
+    // Sven Co-op's ScoreInfo is longer than the SDK's, so the rest goes in `extra`.
+    // We don't know its extra fields, so six made-up bytes stand in for them.
+    #[test]
+    fn keeps_extra_bytes() {
+        let bytes = [3, 12, 0, 0, 0, 0, 0, 2, 0, 0x00, 0x00, 0x20, 0x41, 100, 0];
+        let message = decode(b"ScoreInfo", &bytes).unwrap();
+
+        match &message {
+            Message::ScoreInfo(score_info) => assert_eq!(score_info.extra, bytes[9..]),
+            _ => panic!("{:?}", message),
+        }
+
+        assert_eq!(message.encode().unwrap(), bytes);
+    }
+
+    #[test]
+    fn short_payloads_are_errors() {
+        assert!(matches!(
+            decode(b"ScoreInfo", &[3, 12, 0]),
+            Err(Error::Read { name: "ScoreInfo", source: message::Error::Overflow { offset: 3, wanted: 2, len: 3 } })
+        ));
+
+        assert!(matches!(decode(b"Health", &[]), Err(Error::Read { name: "Health", .. })));
+    }
+}
@@ -0,0 +1,44 @@
+// User messages on the wire, in the encodings of the SDK's parsemsg.cpp.
+
+// Each decoder only uses some of the reads and writes.
+#![allow(dead_code)]
+
+use thiserror::Error;
+
+pub mod capture;
+mod decode;
+mod reader;
+mod writer;
+
+// Only the hook and the replay tool use these.
+#[cfg_attr(not(windows), allow(unused_imports))]
+pub use capture::{CaptureReader, CaptureWriter, Record};
+#[cfg_attr(not(windows), allow(unused_imports))]
+pub use decode::{decode, Error as DecodeError, Message};
+pub use reader::MsgReader;
+pub use writer::MsgWriter;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum Error {
+    #[error("{wanted} bytes at offset {offset} do not fit in the {len}-byte message")]
+    Overflow {
+        offset: usize,
+        wanted: usize,
+        len: usize,
+    },
+
+    #[error("the message has a negative size of {0} bytes")]
+    NegativeSize(i32),
+
+    #[error("a message string can't contain {0:#04x}, because the reader would end the string there")]
+    Terminator(u8),
+}
+
+// READ_STRING copies into a 2048-byte buffer, so it stops after this many characters.
+// The rest of a longer string stays in the message, where the next read finds it.
+const MAX_STRING_LEN: usize = 2047;
+
+// The fixed-point scales of the coordinate and angle encodings.
+const COORD_SCALE: f32 = 8.0;
+const ANGLE_SCALE: f32 = 256.0 / 360.0;
+const HIRES_ANGLE_SCALE: f32 = 65536.0 / 360.0;
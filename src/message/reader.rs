@@ -0,0 +1,126 @@
+use super::{Error, ANGLE_SCALE, COORD_SCALE, HIRES_ANGLE_SCALE, MAX_STRING_LEN};
+
+use std::os::raw::{c_int, c_void};
+use std::slice;
+
+use bstr::BStr;
+
+// BEGIN_READ and the READ_* functions, with an error for every read past the end instead of READ_OK.
+//
+//      let mut reader = MsgReader::new(bytes);
+//      let health = reader.read_byte()?;
+//      let origin = [reader.read_coord()?, reader.read_coord()?, reader.read_coord()?];
+pub struct MsgReader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> MsgReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> MsgReader<'a> {
+        MsgReader { bytes, offset: 0 }
+    }
+
+    // The arguments of a pfnUserMsgHook.
+    pub unsafe fn from_raw(buf: *const c_void, size: c_int) -> Result<MsgReader<'a>, Error> {
+        if size < 0 {
+            return Err(Error::NegativeSize(size));
+        }
+
+        #[allow(clippy::cast_sign_loss)]
+        let size = size as usize;
+
+        if size == 0 || buf.is_null() {
+            return Ok(MsgReader::new(&[]));
+        }
+
+        Ok(MsgReader::new(slice::from_raw_parts(buf.cast(), size)))
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.bytes.len() - self.offset
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.remaining() == 0
+    }
+
+    // The bytes that we haven't read yet. Reading them ends the message.
+    pub fn read_rest(&mut self) -> &'a [u8] {
+        let rest = &self.bytes[self.offset..];
+        self.offset = self.bytes.len();
+        rest
+    }
+
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let overflow = Error::Overflow {
+            offset: self.offset,
+            wanted: N,
+            len: self.bytes.len(),
+        };
+
+        let mut taken = [0; N];
+        taken.copy_from_slice(self.bytes.get(self.offset..self.offset + N).ok_or(overflow)?);
+
+        self.offset += N;
+        Ok(taken)
+    }
+
+    pub fn read_char(&mut self) -> Result<i8, Error> {
+        Ok(i8::from_le_bytes(self.take()?))
+    }
+
+    pub fn read_byte(&mut self) -> Result<u8, Error> {
+        Ok(u8::from_le_bytes(self.take()?))
+    }
+
+    pub fn read_short(&mut self) -> Result<i16, Error> {
+        Ok(i16::from_le_bytes(self.take()?))
+    }
+
+    // READ_WORD is READ_SHORT, which sign-extends. A word is unsigned here.
+    pub fn read_word(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_le_bytes(self.take()?))
+    }
+
+    pub fn read_long(&mut self) -> Result<i32, Error> {
+        Ok(i32::from_le_bytes(self.take()?))
+    }
+
+    // READ_FLOAT forgot its bounds check. We didn't.
+    pub fn read_float(&mut self) -> Result<f32, Error> {
+        Ok(f32::from_le_bytes(self.take()?))
+    }
+
+    // A string ends at a nul or a 0xFF, which we consume, or at the end of the message, which isn't an error.
+    // READ_STRING stops at 0xFF because READ_CHAR returns it as -1, its value for a read past the end.
+    // It returns a Result anyway, like the other reads.
+    #[allow(clippy::unnecessary_wraps)]
+    pub fn read_string(&mut self) -> Result<&'a BStr, Error> {
+        let rest = &self.bytes[self.offset..];
+        let len = rest.iter().take(MAX_STRING_LEN).take_while(|&&byte| !is_terminator(byte)).count();
+        let terminated = rest.get(len).is_some_and(|&byte| is_terminator(byte));
+
+        self.offset += len + usize::from(terminated);
+        Ok(rest[..len].into())
+    }
+
+    pub fn read_coord(&mut self) -> Result<f32, Error> {
+        Ok(f32::from(self.read_short()?) / COORD_SCALE)
+    }
+
+    pub fn read_angle(&mut self) -> Result<f32, Error> {
+        Ok(f32::from(self.read_char()?) / ANGLE_SCALE)
+    }
+
+    pub fn read_hires_angle(&mut self) -> Result<f32, Error> {
+        Ok(f32::from(self.read_short()?) / HIRES_ANGLE_SCALE)
+    }
+}
+
+pub(super) fn is_terminator(byte: u8) -> bool {
+    byte == 0 || byte == 0xFF
+}
@@ -0,0 +1,255 @@
+use super::reader::is_terminator;
+use super::{Error, ANGLE_SCALE, COORD_SCALE, HIRES_ANGLE_SCALE};
+
+// BufferWriter, with a write for every read of MsgReader.
+// A write that doesn't fit returns an error and writes nothing, instead of setting the overflow flag.
+//
+//      let mut writer = MsgWriter::with_limit(192);
+//      writer.write_byte(100)?.write_string(b"weapon_crowbar")?;
+//      let bytes = writer.into_bytes();
+pub struct MsgWriter {
+    bytes: Vec<u8>,
+    limit: usize,
+}
+
+impl Default for MsgWriter {
+    fn default() -> MsgWriter {
+        MsgWriter::new()
+    }
+}
+
+impl MsgWriter {
+    pub fn new() -> MsgWriter {
+        MsgWriter::with_limit(usize::MAX)
+    }
+
+    pub fn with_limit(limit: usize) -> MsgWriter {
+        MsgWriter {
+            bytes: vec![],
+            limit,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> Result<&mut MsgWriter, Error> {
+        if bytes.len() > self.limit - self.bytes.len() {
+            return Err(Error::Overflow {
+                offset: self.bytes.len(),
+                wanted: bytes.len(),
+                len: self.limit,
+            });
+        }
+
+        self.bytes.extend_from_slice(bytes);
+        Ok(self)
+    }
+
+    pub fn write_char(&mut self, value: i8) -> Result<&mut MsgWriter, Error> {
+        self.write_bytes(&value.to_le_bytes())
+    }
+
+    pub fn write_byte(&mut self, value: u8) -> Result<&mut MsgWriter, Error> {
+        self.write_bytes(&value.to_le_bytes())
+    }
+
+    pub fn write_short(&mut self, value: i16) -> Result<&mut MsgWriter, Error> {
+        self.write_bytes(&value.to_le_bytes())
+    }
+
+    pub fn write_word(&mut self, value: u16) -> Result<&mut MsgWriter, Error> {
+        self.write_bytes(&value.to_le_bytes())
+    }
+
+    pub fn write_long(&mut self, value: i32) -> Result<&mut MsgWriter, Error> {
+        self.write_bytes(&value.to_le_bytes())
+    }
+
+    pub fn write_float(&mut self, value: f32) -> Result<&mut MsgWriter, Error> {
+        self.write_bytes(&value.to_le_bytes())
+    }
+
+    // Writes the nul too.
+    pub fn write_string(&mut self, value: &[u8]) -> Result<&mut MsgWriter, Error> {
+        if let Some(&byte) = value.iter().find(|&&byte| is_terminator(byte)) {
+            return Err(Error::Terminator(byte));
+        }
+
+        self.write_bytes(&[value, &[0]].concat())
+    }
+
+    // The engine's WRITE_COORD, WRITE_ANGLE, and WRITE_HIRESANGLE truncate, and the angles wrap.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn write_coord(&mut self, value: f32) -> Result<&mut MsgWriter, Error> {
+        self.write_short((value * COORD_SCALE) as i32 as i16)
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn write_angle(&mut self, value: f32) -> Result<&mut MsgWriter, Error> {
+        self.write_char((value * ANGLE_SCALE) as i32 as i8)
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn write_hires_angle(&mut self, value: f32) -> Result<&mut MsgWriter, Error> {
+        self.write_short((value * HIRES_ANGLE_SCALE) as i32 as i16)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Error, MsgReader, ANGLE_SCALE, COORD_SCALE, HIRES_ANGLE_SCALE, MAX_STRING_LEN};
+    use super::MsgWriter;
+
+    // xorshift32, so that a failure reproduces.
+    struct Rng(u32);
+
+    impl Rng {
+        fn next(&mut self) -> u32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 17;
+            self.0 ^= self.0 << 5;
+            self.0
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            self.next() as usize % n
+        }
+    }
+
+    // One value of each encoding. The fixed-point ones hold what's on the wire.
+    #[derive(Debug)]
+    enum Field {
+        Char(i8),
+        Byte(u8),
+        Short(i16),
+        Word(u16),
+        Long(i32),
+        Float(u32),
+        Coord(i16),
+        Angle(i8),
+        HiresAngle(i16),
+        String(Vec<u8>),
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    fn field(rng: &mut Rng) -> Field {
+        let bits = rng.next();
+
+        match rng.below(10) {
+            0 => Field::Char(bits as i8),
+            1 => Field::Byte(bits as u8),
+            2 => Field::Short(bits as i16),
+            3 => Field::Word(bits as u16),
+            4 => Field::Long(bits as i32),
+            5 => Field::Float(bits),
+            6 => Field::Coord(bits as i16),
+            7 => Field::Angle(bits as i8),
+            8 => Field::HiresAngle(bits as i16),
+
+            _ => {
+                let len = if rng.below(8) == 0 { MAX_STRING_LEN } else { rng.below(16) };
+                Field::String((0..len).map(|_| 1 + rng.below(0xFE) as u8).collect())
+            }
+        }
+    }
+
+    fn write(writer: &mut MsgWriter, field: &Field) -> Result<(), Error> {
+        match *field {
+            Field::Char(value) => writer.write_char(value),
+            Field::Byte(value) => writer.write_byte(value),
+            Field::Short(value) => writer.write_short(value),
+            Field::Word(value) => writer.write_word(value),
+            Field::Long(value) => writer.write_long(value),
+            Field::Float(bits) => writer.write_float(f32::from_bits(bits)),
+            Field::Coord(wire) => writer.write_coord(f32::from(wire) / COORD_SCALE),
+            Field::Angle(wire) => writer.write_angle(f32::from(wire) / ANGLE_SCALE),
+            Field::HiresAngle(wire) => writer.write_hires_angle(f32::from(wire) / HIRES_ANGLE_SCALE),
+            Field::String(ref value) => writer.write_string(value),
+        }
+        .map(|_| ())
+    }
+
+    fn check(reader: &mut MsgReader, field: &Field) {
+        match *field {
+            Field::Char(value) => assert_eq!(reader.read_char(), Ok(value)),
+            Field::Byte(value) => assert_eq!(reader.read_byte(), Ok(value)),
+            Field::Short(value) => assert_eq!(reader.read_short(), Ok(value)),
+            Field::Word(value) => assert_eq!(reader.read_word(), Ok(value)),
+            Field::Long(value) => assert_eq!(reader.read_long(), Ok(value)),
+            Field::Float(bits) => assert_eq!(reader.read_float().map(f32::to_bits), Ok(bits)),
+            Field::Coord(wire) => assert_eq!(reader.read_coord(), Ok(f32::from(wire) / COORD_SCALE)),
+            Field::Angle(wire) => assert_eq!(reader.read_angle(), Ok(f32::from(wire) / ANGLE_SCALE)),
+            Field::HiresAngle(wire) => assert_eq!(reader.read_hires_angle(), Ok(f32::from(wire) / HIRES_ANGLE_SCALE)),
+            Field::String(ref value) => assert_eq!(reader.read_string().map(|string| string.to_vec()), Ok(value.clone())),
+        }
+    }
+
+    #[test]
+    fn every_encoding_round_trips() {
+        let mut rng = Rng(0x2545_F491);
+
+        for _ in 0..2000 {
+            let fields = (0..=rng.below(8)).map(|_| field(&mut rng)).collect::<Vec<_>>();
+            let mut writer = MsgWriter::new();
+
+            for field in &fields {
+                write(&mut writer, field).unwrap();
+            }
+
+            let mut reader = MsgReader::new(writer.as_bytes());
+
+            for field in &fields {
+                check(&mut reader, field);
+            }
+
+            assert!(reader.is_empty(), "{:?}", fields);
+        }
+    }
+
+    #[test]
+    fn fixed_point_values_land_within_a_step() {
+        let mut rng = Rng(0x9E37_79B9);
+
+        for _ in 0..2000 {
+            #[allow(clippy::cast_precision_loss)]
+            let value = (rng.next() as f32 / u32::MAX as f32 - 0.5) * 8000.0;
+            let angle = value % 180.0;
+
+            let mut writer = MsgWriter::new();
+            writer.write_coord(value).unwrap().write_angle(angle).unwrap().write_hires_angle(angle).unwrap();
+
+            let mut reader = MsgReader::new(writer.as_bytes());
+            assert!((reader.read_coord().unwrap() - value).abs() < 1.0 / COORD_SCALE, "{}", value);
+            assert!((reader.read_angle().unwrap() - angle).abs() < 1.0 / ANGLE_SCALE, "{}", angle);
+            assert!((reader.read_hires_angle().unwrap() - angle).abs() < 1.0 / HIRES_ANGLE_SCALE, "{}", angle);
+        }
+    }
+
+    #[test]
+    fn strings_end_at_a_nul_or_0xff() {
+        let mut reader = MsgReader::new(b"ab\xFFcd\0ef");
+        assert_eq!(reader.read_string().unwrap(), "ab");
+        assert_eq!(reader.read_string().unwrap(), "cd");
+        assert_eq!(reader.read_string().unwrap(), "ef");
+        assert!(reader.is_empty());
+
+        let mut writer = MsgWriter::new();
+        assert_eq!(writer.write_string(b"a\xFFb").err(), Some(Error::Terminator(0xFF)));
+        assert_eq!(writer.write_string(b"a\0b").err(), Some(Error::Terminator(0)));
+        assert!(writer.is_empty());
+    }
+}
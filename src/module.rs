@@ -1,4 +1,8 @@
 use crate::memory;
+use crate::pe;
+use crate::resolver;
+use crate::scanner::Scanner;
+use crate::signature::Signature;
 use crate::wide_format;
 
 use std::ffi::CString;
@@ -30,6 +34,9 @@ pub enum ErrorKind<'a> {
 
     #[error("CreateInterface returned a bad pointer for the interface \"{0}\": {1}")]
     BadInterface(&'a str, memory::Error),
+
+    #[error("failed to parse the PE headers: {0}")]
+    Pe(pe::Error),
 }
 
 #[derive(Error, Debug)]
@@ -55,6 +62,7 @@ pub struct Module {
     pub base: usize,
     pub size: usize,
     pub end: usize,
+    pub sections: Vec<pe::Section>,
 }
 
 impl Module {
@@ -70,12 +78,21 @@ impl Module {
         let base = info.lpBaseOfDll as usize;
         let size = info.SizeOfImage as usize;
 
+        let sections = {
+            let memory = unsafe { std::slice::from_raw_parts(base as *const u8, size) };
+
+            pe::Image::parse(memory, pe::Layout::Mapped)
+                .map(|image| image.sections().to_vec())
+                .map_err(|e| Error::new(name, ErrorKind::Pe(e)))?
+        };
+
         let module = Module {
             module,
             name: String::from(name),
             base,
             size,
             end: base + size,
+            sections,
         };
         
         Ok(module)
@@ -111,36 +128,66 @@ impl Module {
         }
     }
 
-    pub fn find_bytes(&self, find_me: &[u8]) -> Option<*const u8> {
-        let memory = unsafe {
+    pub fn memory(&self) -> &[u8] {
+        unsafe {
             let base = self.base as *const u8;
             std::slice::from_raw_parts(base, self.size)
-        };
-    
-        memory
-            .windows(find_me.len())
-            .find(|window| *window == find_me)
-            .map(|window| window.as_ptr())
+        }
+    }
+
+    pub fn find_bytes(&self, find_me: &[u8]) -> Option<*const u8> {
+        let find_me = Signature::from_bytes(find_me);
+
+        Scanner::new(&[&find_me])
+            .first(self.memory())[0]
+            .map(|offset| (self.base + offset) as *const u8)
+    }
+
+    // Returns the address and mapped bytes of each section that `is_wanted` accepts.
+    pub fn section_memory<'m>(&'m self, is_wanted: impl Fn(&pe::Section) -> bool + 'm)
+        -> impl Iterator<Item = (usize, &'m [u8])> + 'm {
+
+        let memory = self.memory();
+
+        self.sections
+            .iter()
+            .filter(move |section| is_wanted(section))
+            .filter_map(move |section| {
+                let rvas = section.rvas();
+                let start = rvas.start as usize;
+                let end = (rvas.end as usize).min(memory.len());
+                memory.get(start..end).map(|bytes| (self.base + start, bytes))
+            })
+    }
+
+    // The module's bytes for offset recipes, with instruction searches limited to executable sections.
+    // `xref::find` and `xref::find_string` take this to find references in the module.
+    pub fn resolver_memory(&self) -> resolver::Memory {
+        let code = self
+            .section_memory(pe::Section::is_executable)
+            .map(|(address, bytes)| address..address + bytes.len());
+
+        resolver::Memory::new(self.base, self.memory()).with_code(code)
+    }
+
+    // Like `find_bytes`, but only searches the sections that `is_wanted` accepts.
+    pub fn find_bytes_in(&self, find_me: &[u8], is_wanted: impl Fn(&pe::Section) -> bool) -> Option<*const u8> {
+        let find_me = Signature::from_bytes(find_me);
+        let scanner = Scanner::new(&[&find_me]);
+
+        self.section_memory(is_wanted)
+            .find_map(|(address, bytes)| scanner.first(bytes)[0].map(|offset| address + offset))
+            .map(|address| address as *const u8)
     }
 
     pub fn find_string(&self, string: &str) -> Option<*const u8> {
         self.find_bytes(string.as_bytes())
     }
 
-    pub fn _find_pattern(&self, pattern: &[Option<u8>]) -> Option<usize> {
-        let memory = unsafe {
-            let base = self.base as *const u8;
-            std::slice::from_raw_parts(base, self.size)
-        };
-
-        memory
-            .windows(pattern.len())
-            .find(|window|
-                pattern
-                    .iter()
-                    .zip(window.iter())
-                    .all(|(pattern_byte, module_byte)| pattern_byte.map_or(true, |p| p == *module_byte)))
-            .map(|window| window.as_ptr() as usize)
+    pub fn _find_pattern(&self, pattern: &Signature) -> Option<usize> {
+        Scanner::new(&[pattern])
+            .first(self.memory())[0]
+            .map(|offset| self.base + offset)
     }
 }
 
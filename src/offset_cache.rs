@@ -0,0 +1,343 @@
+#[cfg(windows)]
+use crate::module::Module;
+#[cfg(windows)]
+use crate::pe;
+use crate::resolver::{self, Recipe};
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Write as _};
+use std::fs;
+use std::hash::Hasher;
+use std::io;
+use std::path::PathBuf;
+
+use log::{info, warn};
+use rustc_hash::FxHasher;
+
+// Identifies one build of a module.
+// The code hash changes with a game update, and also when the module is rebased,
+// because relocations rewrite absolute addresses in the code.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Key {
+    module: String,
+    size: usize,
+    code_hash: u64,
+}
+
+impl Key {
+    fn new<'c>(module: &str, size: usize, code: impl IntoIterator<Item = &'c [u8]>) -> Key {
+        let mut hasher = FxHasher::default();
+
+        for code in code {
+            hasher.write(code);
+        }
+
+        Key {
+            module: String::from(module),
+            size,
+            code_hash: hasher.finish(),
+        }
+    }
+
+    #[cfg(windows)]
+    pub fn from(module: &Module) -> Key {
+        let code = module.section_memory(pe::Section::is_executable).map(|(_, code)| code);
+        Key::new(&module.name, module.size, code)
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (size {:#x}, code hash {:#018x})", self.module, self.size, self.code_hash)
+    }
+}
+
+// Remembers where each recipe's slot lives, as an RVA, so the next attach can skip the scan.
+//
+//      module hw.dll
+//      size 0x2f4e000
+//      code_hash 0x0123456789abcdef
+//      ENGINE_FUNCS 0x1a2b3c
+//      end
+//
+// The `end` line tells a cut-off file from a complete one.
+pub struct OffsetCache {
+    path: PathBuf,
+    key: Key,
+    rvas: BTreeMap<String, usize>,
+    dirty: bool,
+}
+
+impl OffsetCache {
+    pub fn load(path: impl Into<PathBuf>, key: Key) -> OffsetCache {
+        let path = path.into();
+
+        let rvas = match fs::read_to_string(&path) {
+            Ok(text) => match cached(&text, &key) {
+                Ok(rvas) => {
+                    info!("Loaded {} cached offsets for {}.", rvas.len(), key);
+                    rvas
+                }
+
+                Err(Stale::Mismatch(cached_key)) => {
+                    warn!("The offset cache is for {cached_key}, but the module is now {key}. Did the game update? Rescanning.");
+                    BTreeMap::new()
+                }
+
+                Err(Stale::Malformed) => {
+                    warn!("Ignoring the malformed offset cache {path:?}.");
+                    BTreeMap::new()
+                }
+            },
+
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                info!("No offset cache at {path:?} yet.");
+                BTreeMap::new()
+            }
+
+            Err(e) => {
+                warn!("Failed to read the offset cache {path:?}: {e}");
+                BTreeMap::new()
+            }
+        };
+
+        OffsetCache {
+            path,
+            key,
+            rvas,
+            dirty: false,
+        }
+    }
+
+    // Resolves `recipe` from its cached slot when the slot still yields a value that `is_valid` accepts.
+    // Otherwise, resolves `recipe` with a full scan and caches the new slot.
+    pub fn resolve(&mut self, memory: &resolver::Memory, recipe: &Recipe,
+                   is_valid: impl Fn(usize) -> bool) -> Result<usize, resolver::Error> {
+
+        let cached = self
+            .rvas
+            .get(recipe.name)
+            .and_then(|&rva| Some((rva, memory.base().checked_add(rva)?)));
+
+        if let Some((rva, slot)) = cached {
+            match memory.finish(recipe, slot) {
+                Ok(value) if is_valid(value) => return Ok(value),
+                Ok(value) => warn!("The cached slot of {} at RVA {:#x} holds a bad value {:#x}. Rescanning.",
+                                   recipe.name, rva, value),
+                Err(e) => warn!("The cached slot of {} at RVA {:#x} failed: {}. Rescanning.", recipe.name, rva, e),
+            }
+        }
+
+        let slot = memory.locate(recipe)?;
+
+        // A recipe that ends on a pointer out of the module, e.g. into the heap, may land below the module.
+        // An RVA can't describe that slot, so we scan for it every time.
+        if let Some(rva) = slot.checked_sub(memory.base()) {
+            if self.rvas.insert(String::from(recipe.name), rva) != Some(rva) {
+                self.dirty = true;
+            }
+        } else {
+            warn!("The slot of {} at {:#x} is below the module, so we can't cache it.", recipe.name, slot);
+
+            if self.rvas.remove(recipe.name).is_some() {
+                self.dirty = true;
+            }
+        }
+
+        memory.finish(recipe, slot)
+    }
+
+    pub fn save(&mut self) -> io::Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+
+        fs::write(&self.path, format(&self.key, &self.rvas))?;
+        self.dirty = false;
+
+        info!("Saved {} offsets for {} to {:?}.", self.rvas.len(), self.key, self.path);
+        Ok(())
+    }
+}
+
+// Why we can't use a cache file.
+#[derive(Debug, PartialEq)]
+enum Stale {
+    Malformed,
+
+    // The cache is for this other build of the module.
+    Mismatch(Key),
+}
+
+// The RVAs in `text`, if it's a complete cache for `key`.
+fn cached(text: &str, key: &Key) -> Result<BTreeMap<String, usize>, Stale> {
+    match parse(text) {
+        Some((cached_key, rvas)) if cached_key == *key => Ok(rvas),
+        Some((cached_key, _)) => Err(Stale::Mismatch(cached_key)),
+        None => Err(Stale::Malformed),
+    }
+}
+
+fn format(key: &Key, rvas: &BTreeMap<String, usize>) -> String {
+    let mut text = String::new();
+    let _ = writeln!(text, "module {}", key.module);
+    let _ = writeln!(text, "size {:#x}", key.size);
+    let _ = writeln!(text, "code_hash {:#018x}", key.code_hash);
+
+    for (name, rva) in rvas {
+        let _ = writeln!(text, "{name} {rva:#x}");
+    }
+
+    text.push_str("end\n");
+    text
+}
+
+fn parse(text: &str) -> Option<(Key, BTreeMap<String, usize>)> {
+    fn hex(value: &str) -> Option<u64> {
+        u64::from_str_radix(value.strip_prefix("0x")?, 16).ok()
+    }
+
+    let mut lines = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let mut words = line.split_whitespace();
+            let pair = (words.next()?, words.next().unwrap_or_default());
+
+            // A third word means the line isn't ours.
+            words.next().map_or(Some(pair), |_| None)
+        });
+
+    let mut field = |name: &str| match lines.next()?? {
+        (key, value) if key == name && !value.is_empty() => Some(value),
+        _ => None,
+    };
+
+    #[allow(clippy::cast_possible_truncation)]
+    let key = Key {
+        module: String::from(field("module")?),
+        size: hex(field("size")?)? as usize,
+        code_hash: hex(field("code_hash")?)?,
+    };
+
+    let mut rvas = BTreeMap::new();
+
+    loop {
+        match lines.next()?? {
+            ("end", "") => break,
+
+            #[allow(clippy::cast_possible_truncation)]
+            (name, rva) => {
+                rvas.insert(String::from(name), hex(rva)? as usize);
+            }
+        }
+    }
+
+    // Nothing may follow the end.
+    if lines.next().is_some() {
+        return None;
+    }
+
+    Some((key, rvas))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cached, format, Key, OffsetCache, Stale};
+    use crate::resolver::{Anchor, Memory, Recipe, Step};
+    use std::collections::BTreeMap;
+    use std::{env, fs, process};
+
+    fn key() -> Key {
+        Key::new("hw.dll", 0x2f4_e000, vec![&[0x55, 0x8B, 0xEC][..], &[0xC3][..]])
+    }
+
+    fn rvas() -> BTreeMap<String, usize> {
+        vec![(String::from("ENGINE_FUNCS"), 0x1a_2b3c), (String::from("USER_MSG"), 0x4b_58d50)].into_iter().collect()
+    }
+
+    #[test]
+    fn round_trips() {
+        assert_eq!(cached(&format(&key(), &rvas()), &key()), Ok(rvas()));
+        assert_eq!(cached(&format(&key(), &BTreeMap::new()), &key()), Ok(BTreeMap::new()));
+    }
+
+    #[test]
+    fn the_code_is_part_of_the_key() {
+        let patched = Key::new("hw.dll", 0x2f4_e000, vec![&[0x55, 0x8B, 0xEC][..], &[0xCC][..]]);
+        assert_ne!(patched, key());
+        assert_eq!(cached(&format(&patched, &rvas()), &key()), Err(Stale::Mismatch(patched)));
+
+        let resized = Key { size: 0x2f5_0000, ..key() };
+        assert_eq!(cached(&format(&resized, &rvas()), &key()), Err(Stale::Mismatch(resized)));
+    }
+
+    #[test]
+    fn rejects_truncated_files() {
+        let text = format(&key(), &rvas());
+
+        // Every cut but the one at the very end loses the `end` line or part of it.
+        for len in 0..text.len() - 1 {
+            assert_eq!(cached(&text[..len], &key()), Err(Stale::Malformed), "{:?}", &text[..len]);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        let text = format(&key(), &rvas());
+
+        for bad in &[
+            text.replace("size 0x", "size "),
+            text.replace("code_hash", "hash"),
+            text.replace("ENGINE_FUNCS 0x1a2b3c", "ENGINE_FUNCS 0x1a2b3c 0x1"),
+            text.replace("ENGINE_FUNCS 0x1a2b3c", "ENGINE_FUNCS"),
+            text.replace("0x1a2b3c", "0x1a2b3g"),
+            text.replace("module hw.dll\n", ""),
+            text.clone() + "ENGINE_FUNCS 0x1\n",
+        ] {
+            assert_eq!(cached(bad, &key()), Err(Stale::Malformed), "{bad:?}");
+        }
+    }
+
+    #[test]
+    fn resolves_from_the_cache() {
+        const BASE: usize = 0x0100_0000;
+
+        const HEAD: Recipe = Recipe {
+            name: "HEAD",
+            anchor: Anchor::Signature("8B 35"),
+            steps: &[Step::Displacement, Step::Deref],
+        };
+
+        // nop; mov esi,dword ptr ds:[1000010]; ret
+        let bytes = [0x90, 0x8B, 0x35, 0x10, 0x00, 0x00, 0x01, 0xC3];
+
+        // mov edi instead, so only the cached slot finds the address.
+        let mut moved = bytes;
+        moved[2] = 0x3D;
+
+        let path = env::temp_dir().join(format!("offset_cache_test_{}.txt", process::id()));
+        let _ = fs::remove_file(&path);
+
+        let mut cache = OffsetCache::load(path.clone(), key());
+        assert_eq!(cache.resolve(&Memory::new(BASE, &bytes), &HEAD, |_| true).unwrap(), BASE + 0x10);
+        cache.save().unwrap();
+
+        let saved = vec![(String::from("HEAD"), 3)].into_iter().collect();
+        assert_eq!(fs::read_to_string(&path).unwrap(), format(&key(), &saved));
+
+        let mut cache = OffsetCache::load(path.clone(), key());
+        assert_eq!(cache.resolve(&Memory::new(BASE, &moved), &HEAD, |_| true).unwrap(), BASE + 0x10);
+
+        // A value that the caller rejects sends us back to the scan.
+        assert!(cache.resolve(&Memory::new(BASE, &moved), &HEAD, |value| value != BASE + 0x10).is_err());
+
+        // So does a cache for another build.
+        let other = Key { size: 0x2f5_0000, ..key() };
+        let mut cache = OffsetCache::load(path.clone(), other);
+        assert!(cache.resolve(&Memory::new(BASE, &moved), &HEAD, |_| true).is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,162 @@
+// Where we find the engine globals we care about in hw.dll.
+// When a game update moves something, fix the recipe here and read the resolver error in the log.
+
+use crate::resolver::{Anchor, Recipe, Step};
+use crate::xref;
+
+const SCREEN_FADE: Anchor = Anchor::StringXref("ScreenFade", xref::Kind::Push);
+
+/*
+    68 <"ScreenFade">       push offset aScreenfade
+    E8 xx xx xx xx          call ...
+    6A 07                   push 7
+    68 <cl_enginefuncs>     push offset cl_enginefuncs
+    FF 15 <cl_funcs>        call dword ptr ds:[cl_funcs]
+    ...
+    68 <playermove>         push offset playermove      (35 bytes past the anchor)
+*/
+
+pub const ENGINE_FUNCS: Recipe = Recipe {
+    name: "ENGINE_FUNCS",
+    anchor: SCREEN_FADE,
+    steps: &[Step::Next, Step::Next, Step::Next, Step::Expect(&[0x68]), Step::Immediate, Step::Deref],
+};
+
+pub const CLIENT_FUNCS: Recipe = Recipe {
+    name: "client_funcs",
+    anchor: SCREEN_FADE,
+    steps: &[
+        Step::Next,
+        Step::Next,
+        Step::Next,
+        Step::Next,
+        Step::Expect(&[0xFF, 0x15]),
+        Step::Displacement,
+        Step::Deref,
+    ],
+};
+
+pub const PLAYER_MOVE: Recipe = Recipe {
+    name: "PLAYER_MOVE",
+    anchor: SCREEN_FADE,
+    steps: &[Step::Add(35), Step::Expect(&[0x68]), Step::Immediate, Step::Deref],
+};
+
+// Starts at pfnHookUserMsg, so the anchor is a placeholder until we know ENGINE_FUNCS.
+pub const USER_MSG: Recipe = Recipe {
+    name: "USER_MSG",
+    anchor: Anchor::Address(0),
+    steps: &[
+        /*
+            FF 74 24 08     hook_user_msg:  push dword ptr ss:[esp+8]
+            FF 74 24 08                     push dword ptr ss:[esp+8]
+            E8 83 25 01 00  call_inner:     call hw.421C800
+        */
+        Step::Next,
+        Step::Next,
+        Step::FollowRel32,
+
+        /*
+            53              inner_function: push ebx
+            8B 5C 24 08                     mov ebx,dword ptr ss:[esp+8]
+            55                              push ebp
+            8B 6C 24 10                     mov ebp,dword ptr ss:[esp+10]
+            56                              push esi
+            8B 35 50 8D B5 04   load_head:  mov esi,dword ptr ds:[4B58D50]
+        */
+        Step::Next,
+        Step::Next,
+        Step::Next,
+        Step::Next,
+        Step::Next,
+        Step::Expect(&[0x8B, 0x35]),
+        Step::Displacement,
+
+        // The operand is the address of the list head.
+        // We keep the head rather than the first message, because a new message goes in front.
+        Step::Deref,
+    ],
+};
+
+#[cfg(test)]
+mod tests {
+    use super::{CLIENT_FUNCS, ENGINE_FUNCS, PLAYER_MOVE, USER_MSG};
+    use crate::resolver::{Anchor, ErrorKind, Memory, Recipe};
+
+    const BASE: usize = 0x0100_0000;
+
+    // The code around the "ScreenFade" push that the recipes expect, then the string.
+    fn memory() -> Vec<u8> {
+        let mut bytes = vec![
+            0x68, 0x00, 0x01, 0x00, 0x01,       // push offset aScreenfade
+            0xE8, 0x10, 0x00, 0x00, 0x00,       // call hw.1000020
+            0x6A, 0x07,                         // push 7
+            0x68, 0x00, 0x02, 0x00, 0x01,       // push offset cl_enginefuncs
+            0xFF, 0x15, 0x00, 0x03, 0x00, 0x01, // call dword ptr ds:[cl_funcs]
+            0x83, 0xC4, 0x0C,                   // add esp,C
+            0x85, 0xC0,                         // test eax,eax
+            0x75, 0x05,                         // jne
+            0xE8, 0x00, 0x00, 0x00, 0x00,       // call
+            0x68, 0x00, 0x04, 0x00, 0x01,       // push offset playermove
+            0xC3,                               // ret
+        ];
+
+        bytes.resize(0x100, 0xCC);
+        bytes.extend_from_slice(b"ScreenFade\0");
+        bytes
+    }
+
+    #[test]
+    fn recipes_resolve() {
+        let bytes = memory();
+        let memory = Memory::new(BASE, &bytes).with_code(Some(BASE..BASE + 0x100));
+
+        assert_eq!(memory.resolve(&ENGINE_FUNCS).unwrap(), 0x0100_0200);
+        assert_eq!(memory.resolve(&CLIENT_FUNCS).unwrap(), 0x0100_0300);
+        assert_eq!(memory.resolve(&PLAYER_MOVE).unwrap(), 0x0100_0400);
+    }
+
+    #[test]
+    fn recipes_fail_when_the_code_moves() {
+        // An extra byte after `push 7` shifts the code after it, but not the string.
+        let mut bytes = memory();
+        bytes.insert(12, 0x90);
+        bytes.remove(0x100);
+        let memory = Memory::new(BASE, &bytes).with_code(Some(BASE..BASE + 0x100));
+
+        for recipe in &[ENGINE_FUNCS, CLIENT_FUNCS, PLAYER_MOVE] {
+            let error = memory.resolve(recipe).unwrap_err();
+            assert!(matches!(error.kind, ErrorKind::Unexpected { .. }), "{}", error);
+        }
+    }
+
+    #[test]
+    fn user_msg_resolves_from_hook_user_msg() {
+        let mut bytes = vec![
+            0xFF, 0x74, 0x24, 0x08,             // 00: push dword ptr ss:[esp+8]
+            0xFF, 0x74, 0x24, 0x08,             // 04: push dword ptr ss:[esp+8]
+            0xE8, 0x13, 0x00, 0x00, 0x00,       // 08: call hw.1000020
+        ];
+
+        bytes.resize(0x20, 0xCC);
+        bytes.extend_from_slice(&[
+            0x53,                               // 20: push ebx
+            0x8B, 0x5C, 0x24, 0x08,             // 21: mov ebx,dword ptr ss:[esp+8]
+            0x55,                               // 25: push ebp
+            0x8B, 0x6C, 0x24, 0x10,             // 26: mov ebp,dword ptr ss:[esp+10]
+            0x56,                               // 2A: push esi
+            0x8B, 0x35, 0x00, 0x05, 0x00, 0x01, // 2B: mov esi,dword ptr ds:[1000500]
+            0x5E,                               // 31: pop esi
+            0xC3,                               // 32: ret
+        ]);
+
+        let memory = Memory::new(BASE, &bytes);
+        let recipe = Recipe { anchor: Anchor::Address(BASE), ..USER_MSG };
+        assert_eq!(memory.resolve(&recipe).unwrap(), 0x0100_0500);
+
+        // Without the rest of the prologue, the mov is somewhere else.
+        bytes.remove(0x25);
+        let memory = Memory::new(BASE, &bytes);
+        assert!(matches!(memory.resolve(&recipe).unwrap_err().kind, ErrorKind::Unexpected { .. }));
+    }
+}
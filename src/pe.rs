@@ -0,0 +1,696 @@
+use std::convert::TryInto;
+use std::ops::Range;
+
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("the image ends before the {what} at offset {offset:#x}")]
+    OutOfBounds {
+        what: &'static str,
+        offset: usize,
+    },
+
+    #[error("bad DOS signature {0:#x}; expected \"MZ\"")]
+    DosSignature(u16),
+
+    #[error("bad NT signature {0:#x}; expected \"PE\\0\\0\"")]
+    NtSignature(u32),
+
+    #[error("optional header magic {0:#x} is not PE32 (0x10b)")]
+    NotPe32(u16),
+
+    #[error("the RVA {0:#x} is not backed by the headers or any section")]
+    UnmappedRva(u32),
+
+    #[error("the string at RVA {0:#x} has no null terminator")]
+    UnterminatedString(u32),
+
+    #[error("the {what} at RVA {rva:#x} runs past the end of the address space")]
+    Overflow {
+        what: &'static str,
+        rva: u32,
+    },
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+// How the bytes we parse are laid out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Layout {
+    // Loaded by the Windows loader, where an RVA is an offset from the image base.
+    Mapped,
+
+    // Read from disk, where we translate RVAs through the section table.
+    File,
+}
+
+const IMAGE_DOS_SIGNATURE: u16 = 0x5A4D;
+const IMAGE_NT_SIGNATURE: u32 = 0x0000_4550;
+const IMAGE_NT_OPTIONAL_HDR32_MAGIC: u16 = 0x10B;
+
+const IMAGE_DIRECTORY_ENTRY_EXPORT: usize = 0;
+const IMAGE_DIRECTORY_ENTRY_IMPORT: usize = 1;
+
+const IMAGE_SIZEOF_FILE_HEADER: usize = 20;
+const IMAGE_SIZEOF_SECTION_HEADER: usize = 40;
+const IMAGE_SIZEOF_IMPORT_DESCRIPTOR: usize = 20;
+
+const IMAGE_ORDINAL_FLAG32: u32 = 0x8000_0000;
+
+pub const IMAGE_SCN_MEM_EXECUTE: u32 = 0x2000_0000;
+pub const IMAGE_SCN_MEM_READ: u32 = 0x4000_0000;
+pub const IMAGE_SCN_MEM_WRITE: u32 = 0x8000_0000;
+
+#[derive(Clone, Debug)]
+pub struct Section {
+    pub name: String,
+    pub virtual_address: u32,
+    pub virtual_size: u32,
+    pub raw_offset: u32,
+    pub raw_size: u32,
+    pub characteristics: u32,
+}
+
+impl Section {
+    pub fn is_executable(&self) -> bool {
+        self.characteristics & IMAGE_SCN_MEM_EXECUTE != 0
+    }
+
+    pub fn is_readable(&self) -> bool {
+        self.characteristics & IMAGE_SCN_MEM_READ != 0
+    }
+
+    pub fn is_writable(&self) -> bool {
+        self.characteristics & IMAGE_SCN_MEM_WRITE != 0
+    }
+
+    pub fn is_read_only_data(&self) -> bool {
+        self.is_readable() && !self.is_writable() && !self.is_executable()
+    }
+
+    // The RVAs this section occupies once mapped.
+    pub fn rvas(&self) -> Range<u32> {
+        let size = if self.virtual_size == 0 { self.raw_size } else { self.virtual_size };
+        self.virtual_address..self.virtual_address.saturating_add(size)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum ExportTarget {
+    Rva(u32),
+
+    // The export lives in another module, e.g. "NTDLL.RtlAllocateHeap".
+    Forwarder(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct Export {
+    pub name: Option<String>,
+    pub ordinal: u32,
+    pub target: ExportTarget,
+}
+
+#[derive(Clone, Debug)]
+pub enum ImportSymbol {
+    Name { hint: u16, name: String },
+    Ordinal(u16),
+
+    // A bound, mapped image without an import lookup table only keeps resolved addresses in its IAT.
+    Unknown,
+}
+
+#[derive(Clone, Debug)]
+pub struct ImportedFunction {
+    pub symbol: ImportSymbol,
+
+    // Where the loader writes the address of this function.
+    pub iat_rva: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct Import {
+    pub module: String,
+    pub functions: Vec<ImportedFunction>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct DataDirectory {
+    rva: u32,
+    size: u32,
+}
+
+impl DataDirectory {
+    fn is_present(self) -> bool {
+        self.rva != 0 && self.size != 0
+    }
+
+    fn contains(self, rva: u32) -> bool {
+        rva >= self.rva && rva - self.rva < self.size
+    }
+}
+
+// The fields are named after the ones in the PE headers.
+#[derive(Debug)]
+#[allow(clippy::struct_field_names)]
+pub struct Image<'a> {
+    bytes: &'a [u8],
+    layout: Layout,
+    pub image_base: u32,
+    pub size_of_image: u32,
+    size_of_headers: u32,
+    data_directories: Vec<DataDirectory>,
+    sections: Vec<Section>,
+}
+
+impl<'a> Image<'a> {
+    pub fn parse(bytes: &'a [u8], layout: Layout) -> Result<Image<'a>> {
+        let dos_signature = read_u16(bytes, 0, "DOS header")?;
+
+        if dos_signature != IMAGE_DOS_SIGNATURE {
+            return Err(Error::DosSignature(dos_signature));
+        }
+
+        let nt_headers = read_u32(bytes, 0x3C, "DOS header")? as usize;
+        let nt_signature = read_u32(bytes, nt_headers, "NT headers")?;
+
+        if nt_signature != IMAGE_NT_SIGNATURE {
+            return Err(Error::NtSignature(nt_signature));
+        }
+
+        let file_header = offset(nt_headers, 4, "NT headers")?;
+        let number_of_sections = read_u16(bytes, file_header + 2, "file header")?;
+        let size_of_optional_header = read_u16(bytes, file_header + 16, "file header")?;
+
+        let optional_header = offset(file_header, IMAGE_SIZEOF_FILE_HEADER, "file header")?;
+        let magic = read_u16(bytes, optional_header, "optional header")?;
+
+        if magic != IMAGE_NT_OPTIONAL_HDR32_MAGIC {
+            return Err(Error::NotPe32(magic));
+        }
+
+        let image_base = read_u32(bytes, optional_header + 28, "optional header")?;
+        let size_of_image = read_u32(bytes, optional_header + 56, "optional header")?;
+        let size_of_headers = read_u32(bytes, optional_header + 60, "optional header")?;
+        let number_of_rva_and_sizes = read_u32(bytes, optional_header + 92, "optional header")? as usize;
+
+        let data_directories = (0..number_of_rva_and_sizes)
+            .map(|index| {
+                let entry = index
+                    .checked_mul(8)
+                    .and_then(|entry| entry.checked_add(optional_header + 96))
+                    .ok_or(Error::OutOfBounds { what: "data directory", offset: optional_header })?;
+
+                Ok(DataDirectory {
+                    rva: read_u32(bytes, entry, "data directory")?,
+                    size: read_u32(bytes, entry + 4, "data directory")?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let section_table = offset(optional_header, usize::from(size_of_optional_header), "optional header")?;
+
+        let sections = (0..usize::from(number_of_sections))
+            .map(|index| {
+                let header = offset(section_table, index * IMAGE_SIZEOF_SECTION_HEADER, "section header")?;
+                let name = slice(bytes, header, 8, "section header")?;
+                let name_len = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+
+                Ok(Section {
+                    name: String::from_utf8_lossy(&name[..name_len]).into_owned(),
+                    virtual_size: read_u32(bytes, header + 8, "section header")?,
+                    virtual_address: read_u32(bytes, header + 12, "section header")?,
+                    raw_size: read_u32(bytes, header + 16, "section header")?,
+                    raw_offset: read_u32(bytes, header + 20, "section header")?,
+                    characteristics: read_u32(bytes, header + 36, "section header")?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Image {
+            bytes,
+            layout,
+            image_base,
+            size_of_image,
+            size_of_headers,
+            data_directories,
+            sections,
+        })
+    }
+
+    pub fn sections(&self) -> &[Section] {
+        &self.sections
+    }
+
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|section| section.name == name)
+    }
+
+    // The bytes of `section` as they appear in the slice we parsed.
+    pub fn section_bytes(&self, section: &Section) -> Result<&'a [u8]> {
+        match self.layout {
+            Layout::Mapped => {
+                let rvas = section.rvas();
+                slice(self.bytes, rvas.start as usize, (rvas.end - rvas.start) as usize, "section")
+            }
+
+            Layout::File => slice(self.bytes, section.raw_offset as usize, section.raw_size as usize, "section"),
+        }
+    }
+
+    // Translates an RVA to an offset into the slice we parsed.
+    pub fn offset_of(&self, rva: u32) -> Result<usize> {
+        if self.layout == Layout::Mapped || rva < self.size_of_headers {
+            return Ok(rva as usize);
+        }
+
+        self.sections
+            .iter()
+            .find(|section| section.rvas().contains(&rva))
+            .and_then(|section| {
+                let offset = rva - section.virtual_address;
+
+                if offset < section.raw_size {
+                    (section.raw_offset as usize).checked_add(offset as usize)
+                } else {
+                    None
+                }
+            })
+            .ok_or(Error::UnmappedRva(rva))
+    }
+
+    pub fn read_u16(&self, rva: u32, what: &'static str) -> Result<u16> {
+        read_u16(self.bytes, self.offset_of(rva)?, what)
+    }
+
+    pub fn read_u32(&self, rva: u32, what: &'static str) -> Result<u32> {
+        read_u32(self.bytes, self.offset_of(rva)?, what)
+    }
+
+    pub fn read_string(&self, rva: u32) -> Result<String> {
+        let start = self.offset_of(rva)?;
+
+        let rest = self.bytes.get(start..).ok_or(Error::OutOfBounds {
+            what: "string",
+            offset: start,
+        })?;
+
+        let len = rest
+            .iter()
+            .position(|&c| c == 0)
+            .ok_or(Error::UnterminatedString(rva))?;
+
+        Ok(String::from_utf8_lossy(&rest[..len]).into_owned())
+    }
+
+    fn data_directory(&self, index: usize) -> Option<DataDirectory> {
+        self.data_directories
+            .get(index)
+            .copied()
+            .filter(|directory| directory.is_present())
+    }
+
+    pub fn exports(&self) -> Result<Vec<Export>> {
+        let Some(directory) = self.data_directory(IMAGE_DIRECTORY_ENTRY_EXPORT) else {
+            return Ok(vec![]);
+        };
+
+        let field = |offset| self.read_u32(rva(directory.rva, offset, "export directory")?, "export directory");
+
+        let base = field(16)?;
+        let number_of_functions = field(20)?;
+        let number_of_names = field(24)?;
+        let address_of_functions = field(28)?;
+        let address_of_names = field(32)?;
+        let address_of_name_ordinals = field(36)?;
+
+        // Every function takes four bytes of the address table, so a count that the image can't hold is garbage.
+        if number_of_functions as usize > self.bytes.len() / 4 {
+            return Err(Error::OutOfBounds {
+                what: "export address table",
+                offset: address_of_functions as usize,
+            });
+        }
+
+        let mut names = vec![None; number_of_functions as usize];
+
+        for index in 0..number_of_names {
+            let name = self.read_u32(entry(address_of_names, index, 4, "export name table")?, "export name table")?;
+            let ordinals = entry(address_of_name_ordinals, index, 2, "export ordinal table")?;
+            let function = self.read_u16(ordinals, "export ordinal table")?;
+
+            if let Some(slot) = names.get_mut(usize::from(function)) {
+                *slot = Some(self.read_string(name)?);
+            }
+        }
+
+        let mut exports = vec![];
+
+        for (index, name) in (0..number_of_functions).zip(names) {
+            let rva = self.read_u32(entry(address_of_functions, index, 4, "export address table")?, "export address table")?;
+
+            if rva == 0 {
+                continue;
+            }
+
+            let target = if directory.contains(rva) {
+                ExportTarget::Forwarder(self.read_string(rva)?)
+            } else {
+                ExportTarget::Rva(rva)
+            };
+
+            exports.push(Export {
+                name,
+                ordinal: base.wrapping_add(index),
+                target,
+            });
+        }
+
+        Ok(exports)
+    }
+
+    pub fn imports(&self) -> Result<Vec<Import>> {
+        let Some(directory) = self.data_directory(IMAGE_DIRECTORY_ENTRY_IMPORT) else {
+            return Ok(vec![]);
+        };
+
+        let mut imports = vec![];
+
+        for index in 0.. {
+            #[allow(clippy::cast_possible_truncation)]
+            let descriptor = entry(directory.rva, index, IMAGE_SIZEOF_IMPORT_DESCRIPTOR as u32, "import descriptor")?;
+            let field = |offset| self.read_u32(rva(descriptor, offset, "import descriptor")?, "import descriptor");
+
+            let lookup_table = field(0)?;
+            let name = field(12)?;
+            let address_table = field(16)?;
+
+            if name == 0 && address_table == 0 {
+                break;
+            }
+
+            // The loader overwrites the IAT of a mapped image, so only the lookup table still has names.
+            let thunks = if lookup_table != 0 {
+                Some(lookup_table)
+            } else if self.layout == Layout::File {
+                Some(address_table)
+            } else {
+                None
+            };
+
+            let mut functions = vec![];
+
+            for index in 0.. {
+                let iat_rva = entry(address_table, index, 4, "import address table")?;
+
+                let symbol = if let Some(thunks) = thunks {
+                    let thunk = self.read_u32(entry(thunks, index, 4, "import lookup table")?, "import lookup table")?;
+
+                    if thunk == 0 {
+                        break;
+                    }
+
+                    if thunk & IMAGE_ORDINAL_FLAG32 == 0 {
+                        ImportSymbol::Name {
+                            hint: self.read_u16(thunk, "import hint")?,
+                            name: self.read_string(rva(thunk, 2, "import name")?)?,
+                        }
+                    } else {
+                        #[allow(clippy::cast_possible_truncation)]
+                        ImportSymbol::Ordinal(thunk as u16)
+                    }
+                } else {
+                    if self.read_u32(iat_rva, "import address table")? == 0 {
+                        break;
+                    }
+
+                    ImportSymbol::Unknown
+                };
+
+                functions.push(ImportedFunction { symbol, iat_rva });
+            }
+
+            imports.push(Import {
+                module: self.read_string(name)?,
+                functions,
+            });
+        }
+
+        Ok(imports)
+    }
+}
+
+// `base + offset` for an offset into the bytes we parsed.
+fn offset(base: usize, offset: usize, what: &'static str) -> Result<usize> {
+    base.checked_add(offset).ok_or(Error::OutOfBounds { what, offset: base })
+}
+
+// `base + offset` for an RVA.
+fn rva(base: u32, offset: u32, what: &'static str) -> Result<u32> {
+    base.checked_add(offset).ok_or(Error::Overflow { what, rva: base })
+}
+
+// The RVA of entry `index` in a table of `size`-byte entries at `table`.
+fn entry(table: u32, index: u32, size: u32, what: &'static str) -> Result<u32> {
+    index
+        .checked_mul(size)
+        .and_then(|offset| table.checked_add(offset))
+        .ok_or(Error::Overflow { what, rva: table })
+}
+
+fn slice<'a>(bytes: &'a [u8], offset: usize, len: usize, what: &'static str) -> Result<&'a [u8]> {
+    offset
+        .checked_add(len)
+        .and_then(|end| bytes.get(offset..end))
+        .ok_or(Error::OutOfBounds { what, offset })
+}
+
+fn read_u16(bytes: &[u8], offset: usize, what: &'static str) -> Result<u16> {
+    let bytes = slice(bytes, offset, 2, what)?;
+    Ok(u16::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+fn read_u32(bytes: &[u8], offset: usize, what: &'static str) -> Result<u32> {
+    let bytes = slice(bytes, offset, 4, what)?;
+    Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Error, ExportTarget, Image, ImportSymbol, Layout};
+
+    // A tiny PE32 DLL with one export, from the tests of the libloading crate (ISC license).
+    const NAGISA32: &[u8] = include_bytes!("../tests/fixtures/nagisa32.dll");
+
+    const EXPORT_DIRECTORY: usize = 0x620;
+
+    fn put_u32(bytes: &mut [u8], offset: usize, value: u32) {
+        bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    // Lays the sections out at their RVAs, like the loader does.
+    fn map(file: &[u8]) -> Vec<u8> {
+        let image = Image::parse(file, Layout::File).unwrap();
+        let mut mapped = vec![0; image.size_of_image as usize];
+        mapped[..image.size_of_headers as usize].copy_from_slice(&file[..image.size_of_headers as usize]);
+
+        for section in image.sections() {
+            let bytes = image.section_bytes(section).unwrap();
+            let start = section.virtual_address as usize;
+            let len = bytes.len().min(section.virtual_size as usize);
+            mapped[start..start + len].copy_from_slice(&bytes[..len]);
+        }
+
+        mapped
+    }
+
+    fn check_nagisa32(image: &Image) {
+        assert_eq!(image.image_base, 0x1000_0000);
+
+        let names = image.sections().iter().map(|section| section.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, [".text", ".rdata", ".data", ".reloc"]);
+        assert!(image.section(".text").unwrap().is_executable());
+        assert!(image.section(".rdata").unwrap().is_read_only_data());
+
+        let exports = image.exports().unwrap();
+        assert_eq!(exports.len(), 1);
+        assert_eq!(exports[0].name.as_deref(), Some("windows"));
+        assert_eq!(exports[0].ordinal, 1);
+        assert!(matches!(exports[0].target, ExportTarget::Rva(0x1000)));
+
+        assert!(image.imports().unwrap().is_empty());
+    }
+
+    #[test]
+    fn parses_a_real_dll() {
+        check_nagisa32(&Image::parse(NAGISA32, Layout::File).unwrap());
+
+        let mapped = map(NAGISA32);
+        check_nagisa32(&Image::parse(&mapped, Layout::Mapped).unwrap());
+    }
+
+    #[test]
+    fn truncated_dll_is_an_error() {
+        for len in 0..NAGISA32.len() {
+            let truncated = &NAGISA32[..len];
+
+            let result = Image::parse(truncated, Layout::File).and_then(|image| {
+                let exports = image.exports()?;
+                image.imports()?;
+                Ok(exports)
+            });
+
+            // The export names are the last thing in .rdata that we read.
+            if len < 0x660 {
+                assert!(result.is_err(), "{} bytes", len);
+            }
+        }
+    }
+
+    #[test]
+    fn export_directory_fields_that_overflow_are_errors() {
+        let cases: &[(usize, u32)] = &[
+            // The number of functions, way more than the image can hold.
+            (20, 0xFFFF_FFFF),
+
+            // The address of the name table, so that name 1 is past 4 GiB.
+            (32, 0xFFFF_FFFC),
+
+            // The address of the ordinal table.
+            (36, 0xFFFF_FFFF),
+
+            // The address of the function table.
+            (28, 0xFFFF_FFFC),
+        ];
+
+        for &(field, value) in cases {
+            let mut file = NAGISA32.to_vec();
+            put_u32(&mut file, EXPORT_DIRECTORY + 24, 2);
+            put_u32(&mut file, EXPORT_DIRECTORY + 20, 2);
+            put_u32(&mut file, EXPORT_DIRECTORY + field, value);
+
+            let mapped = map(&file);
+
+            for image in &[Image::parse(&file, Layout::File).unwrap(), Image::parse(&mapped, Layout::Mapped).unwrap()] {
+                assert!(image.exports().is_err(), "field {} = {:#x}", field, value);
+            }
+        }
+
+        // The export directory itself at the very end of the address space.
+        let mut mapped = map(NAGISA32);
+        let data_directory = NAGISA32[0x3C] as usize + 24 + 96;
+        put_u32(&mut mapped, data_directory, 0xFFFF_FFF0);
+
+        assert!(matches!(
+            Image::parse(&mapped, Layout::Mapped).unwrap().exports(),
+            Err(Error::Overflow { what: "export directory", rva: 0xFFFF_FFF0 })
+        ));
+    }
+
+    const OPTIONAL_HEADER: usize = 0x40 + 24;
+
+    // A mapped image that is only headers, with empty data directories.
+    fn headers() -> Vec<u8> {
+        let mut bytes = vec![0; 0x1000];
+        let nt_headers = 0x40;
+
+        bytes[..2].copy_from_slice(b"MZ");
+        put_u32(&mut bytes, 0x3C, 0x40);
+        bytes[nt_headers..nt_headers + 4].copy_from_slice(b"PE\0\0");
+        bytes[nt_headers + 20..nt_headers + 22].copy_from_slice(&224_u16.to_le_bytes());
+        bytes[OPTIONAL_HEADER..OPTIONAL_HEADER + 2].copy_from_slice(&0x10B_u16.to_le_bytes());
+        put_u32(&mut bytes, OPTIONAL_HEADER + 56, 0x1000);
+        put_u32(&mut bytes, OPTIONAL_HEADER + 60, 0x1000);
+        put_u32(&mut bytes, OPTIONAL_HEADER + 92, 16);
+        bytes
+    }
+
+    // The export directory holds the forwarder's name, which is how the loader tells it from code.
+    #[test]
+    fn parses_forwarders() {
+        let mut bytes = headers();
+        put_u32(&mut bytes, OPTIONAL_HEADER + 96, 0x200);
+        put_u32(&mut bytes, OPTIONAL_HEADER + 96 + 4, 0x100);
+
+        put_u32(&mut bytes, 0x200 + 16, 1);
+        put_u32(&mut bytes, 0x200 + 20, 1);
+        put_u32(&mut bytes, 0x200 + 28, 0x240);
+        put_u32(&mut bytes, 0x240, 0x260);
+        bytes[0x260..0x276].copy_from_slice(b"NTDLL.RtlAllocateHeap\0");
+
+        let exports = Image::parse(&bytes, Layout::Mapped).unwrap().exports().unwrap();
+        assert_eq!(exports.len(), 1);
+        assert_eq!(exports[0].name, None);
+        assert_eq!(exports[0].ordinal, 1);
+        assert!(matches!(&exports[0].target, ExportTarget::Forwarder(name) if name == "NTDLL.RtlAllocateHeap"));
+    }
+
+    // A mapped image whose headers hold one import descriptor:
+    // KERNEL32.dll!Sleep by name with hint 5, and ordinal 7.
+    fn image_with_imports() -> Vec<u8> {
+        let mut bytes = headers();
+        put_u32(&mut bytes, OPTIONAL_HEADER + 96 + 8, 0x200);
+        put_u32(&mut bytes, OPTIONAL_HEADER + 96 + 12, 40);
+
+        put_u32(&mut bytes, 0x200, 0x300);
+        put_u32(&mut bytes, 0x200 + 12, 0x280);
+        put_u32(&mut bytes, 0x200 + 16, 0x340);
+        bytes[0x280..0x28D].copy_from_slice(b"KERNEL32.dll\0");
+
+        put_u32(&mut bytes, 0x300, 0x400);
+        put_u32(&mut bytes, 0x304, 0x8000_0007);
+        bytes[0x400..0x402].copy_from_slice(&5_u16.to_le_bytes());
+        bytes[0x402..0x408].copy_from_slice(b"Sleep\0");
+
+        bytes
+    }
+
+    #[test]
+    fn parses_imports() {
+        let bytes = image_with_imports();
+        let imports = Image::parse(&bytes, Layout::Mapped).unwrap().imports().unwrap();
+
+        assert_eq!(imports.len(), 1);
+        assert_eq!(imports[0].module, "KERNEL32.dll");
+
+        let functions = &imports[0].functions;
+        assert_eq!(functions.len(), 2);
+        assert!(matches!(&functions[0].symbol, ImportSymbol::Name { hint: 5, name } if name == "Sleep"));
+        assert_eq!(functions[0].iat_rva, 0x340);
+        assert!(matches!(functions[1].symbol, ImportSymbol::Ordinal(7)));
+        assert_eq!(functions[1].iat_rva, 0x344);
+    }
+
+    #[test]
+    fn import_tables_that_overflow_are_errors() {
+        let cases: &[(usize, u32)] = &[
+            // The import address table, so that entry 1 is past 4 GiB.
+            (0x200 + 16, 0xFFFF_FFFC),
+
+            // The import lookup table.
+            (0x200, 0xFFFF_FFFF),
+
+            // The hint/name entry of the first thunk.
+            (0x300, 0x7FFF_FFFF),
+
+            // The import directory.
+            (0x40 + 24 + 96 + 8, 0xFFFF_FFF0),
+        ];
+
+        for &(offset, value) in cases {
+            let mut bytes = image_with_imports();
+            put_u32(&mut bytes, offset, value);
+
+            for &layout in &[Layout::Mapped, Layout::File] {
+                assert!(Image::parse(&bytes, layout).unwrap().imports().is_err(), "{:#x} = {:#x}", offset, value);
+            }
+        }
+
+        let bytes = image_with_imports();
+
+        for len in 0..0x408 {
+            let result = Image::parse(&bytes[..len], Layout::Mapped).and_then(|image| image.imports());
+            assert!(result.is_err(), "{} bytes", len);
+        }
+    }
+}
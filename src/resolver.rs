@@ -0,0 +1,575 @@
+use crate::disasm::{self, Instruction};
+use crate::scanner::Scanner;
+use crate::signature::{self, Signature};
+use crate::xref;
+
+use std::convert::TryInto;
+use std::fmt;
+use std::ops::Range;
+
+use thiserror::Error;
+
+// Where a recipe starts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Anchor<'a> {
+    // The first instruction of this kind that refers to this string literal.
+    StringXref(&'a str, xref::Kind),
+
+    // The cursor of the first match of this IDA-style signature.
+    Signature(&'a str),
+
+    // An address we already know, such as a function pointer from an engine table.
+    Address(usize),
+}
+
+impl fmt::Display for Anchor<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Anchor::StringXref(string, kind) => write!(f, "{kind} \"{string}\""),
+            Anchor::Signature(signature) => write!(f, "signature \"{signature}\""),
+            Anchor::Address(address) => write!(f, "address {address:#x}"),
+        }
+    }
+}
+
+// One move of the cursor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Step {
+    // Move the cursor by this many bytes.
+    Add(isize),
+
+    // Replace the cursor with the little-endian u32 at the cursor.
+    Deref,
+
+    // The cursor is on an instruction; move past it.
+    Next,
+
+    // The cursor is on a rel32 call, jmp, or jcc; move it to the branch target.
+    FollowRel32,
+
+    // The cursor is on an instruction with a 32-bit displacement, like `mov esi,[imm32]`; move onto the displacement.
+    Displacement,
+
+    // The cursor is on an instruction with a 32-bit immediate, like `push imm32`; move onto the immediate.
+    Immediate,
+
+    // Fail unless these bytes are at the cursor. The cursor stays put.
+    Expect(&'static [u8]),
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Step::Add(n) => write!(f, "add {n}"),
+            Step::Deref => f.write_str("deref u32"),
+            Step::Next => f.write_str("next instruction"),
+            Step::FollowRel32 => f.write_str("follow rel32"),
+            Step::Displacement => f.write_str("disp32 operand"),
+            Step::Immediate => f.write_str("imm32 operand"),
+            Step::Expect(bytes) => {
+                f.write_str("expect")?;
+
+                for byte in *bytes {
+                    write!(f, " {byte:02X}")?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+// How to find one global: start at an anchor and take each step in order.
+#[derive(Clone, Copy, Debug)]
+pub struct Recipe<'a> {
+    pub name: &'a str,
+    pub anchor: Anchor<'a>,
+    pub steps: &'a [Step],
+}
+
+impl Recipe<'_> {
+    // Splits the steps into the ones that find a slot and the trailing derefs that read it.
+    fn split(&self) -> (&[Step], &[Step]) {
+        let derefs = self
+            .steps
+            .iter()
+            .rev()
+            .take_while(|&&step| step == Step::Deref)
+            .count();
+
+        self.steps.split_at(self.steps.len() - derefs)
+    }
+
+    fn error(&self, location: Location, kind: ErrorKind) -> Error {
+        Error {
+            name: String::from(self.name),
+            location,
+            kind,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ErrorKind {
+    #[error("the string was not found")]
+    StringNotFound,
+
+    #[error("no {kind} refers to the string at {string:#x}")]
+    XrefNotFound {
+        string: usize,
+        kind: xref::Kind,
+    },
+
+    #[error("bad signature: {0}")]
+    Signature(#[from] signature::Error),
+
+    #[error("the signature was not found")]
+    SignatureNotFound,
+
+    #[error("the cursor {cursor:#x} moved out of the address space")]
+    Overflow {
+        cursor: usize,
+    },
+
+    #[error("cannot read {len} bytes at {address:#x}, which is outside of the module")]
+    OutOfBounds {
+        address: usize,
+        len: usize,
+    },
+
+    #[error("failed to decode an instruction: {0}")]
+    Decode(#[from] disasm::Error),
+
+    #[error("expected a rel32 branch at {address:#x} but found {bytes:02X?}")]
+    NotARelativeBranch {
+        address: usize,
+        bytes: Vec<u8>,
+    },
+
+    #[error("expected an instruction with a 32-bit {operand} at {address:#x} but found {bytes:02X?}")]
+    MissingOperand {
+        address: usize,
+        operand: &'static str,
+        bytes: Vec<u8>,
+    },
+
+    #[error("expected {expected:02X?} at {address:#x} but found {found:02X?}")]
+    Unexpected {
+        address: usize,
+        expected: &'static [u8],
+        found: Vec<u8>,
+    },
+}
+
+#[derive(Debug)]
+pub enum Location {
+    Anchor(String),
+    Step(usize, Step),
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Location::Anchor(anchor) => write!(f, "anchor ({anchor})"),
+            Location::Step(index, step) => write!(f, "step {index} ({step})"),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+#[error("failed to resolve {name} at {location}: {kind}")]
+pub struct Error {
+    pub name: String,
+    pub location: Location,
+    pub kind: ErrorKind,
+}
+
+// The bytes a recipe may read, and the address they start at.
+pub struct Memory<'m> {
+    base: usize,
+    bytes: &'m [u8],
+
+    // Offsets into `bytes` that hold code. We only look for instructions there.
+    code: Vec<Range<usize>>,
+}
+
+impl<'m> Memory<'m> {
+    // One code range that covers every byte.
+    #[allow(clippy::single_range_in_vec_init)]
+    pub fn new(base: usize, bytes: &'m [u8]) -> Memory<'m> {
+        Memory {
+            base,
+            bytes,
+            code: vec![0..bytes.len()],
+        }
+    }
+
+    // Limits instruction searches to these address ranges.
+    pub fn with_code(mut self, code: impl IntoIterator<Item = Range<usize>>) -> Memory<'m> {
+        let base = self.base;
+        let len = self.bytes.len();
+
+        self.code = code
+            .into_iter()
+            .map(|range| range.start.saturating_sub(base).min(len)..range.end.saturating_sub(base).min(len))
+            .collect();
+
+        self
+    }
+
+    pub fn base(&self) -> usize {
+        self.base
+    }
+
+    pub fn bytes(&self) -> &'m [u8] {
+        self.bytes
+    }
+
+    pub fn code(&self) -> &[Range<usize>] {
+        &self.code
+    }
+
+    pub fn is_code(&self, offset: usize) -> bool {
+        self.code.iter().any(|code| code.contains(&offset))
+    }
+
+    pub fn read(&self, address: usize, len: usize) -> Option<&'m [u8]> {
+        let start = address.checked_sub(self.base)?;
+        let end = start.checked_add(len)?;
+        self.bytes.get(start..end)
+    }
+
+    pub fn read_u32(&self, address: usize) -> Option<u32> {
+        self.read(address, 4)
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn find_in(&self, signature: &Signature, ranges: &[Range<usize>]) -> Option<usize> {
+        let scanner = Scanner::new(&[signature]);
+
+        ranges
+            .iter()
+            .find_map(|range| {
+                let bytes = &self.bytes[range.clone()];
+                scanner.first(bytes)[0].map(|offset| self.base + range.start + offset)
+            })
+    }
+
+    #[allow(clippy::single_range_in_vec_init)]
+    pub fn find(&self, signature: &Signature) -> Option<usize> {
+        self.find_in(signature, &[0..self.bytes.len()])
+    }
+
+    pub fn find_code(&self, signature: &Signature) -> Option<usize> {
+        self.find_in(signature, &self.code)
+    }
+
+    fn anchor(&self, anchor: Anchor) -> Result<usize, ErrorKind> {
+        match anchor {
+            Anchor::StringXref(string, kind) => {
+                let (string, xrefs) = xref::find_string(self, string).ok_or(ErrorKind::StringNotFound)?;
+
+                xrefs
+                    .into_iter()
+                    .find(|xref| xref.kind == kind)
+                    .map(|xref| xref.address)
+                    .ok_or(ErrorKind::XrefNotFound { string, kind })
+            }
+
+            Anchor::Signature(signature) => {
+                let signature = Signature::parse(signature)?;
+                self.find_code(&signature).ok_or(ErrorKind::SignatureNotFound)
+            }
+
+            Anchor::Address(address) => Ok(address),
+        }
+    }
+
+    fn decode(&self, address: usize) -> Result<Instruction, ErrorKind> {
+        let bytes = self
+            .read(address, 1)
+            .map(|_| &self.bytes[address - self.base..])
+            .ok_or(ErrorKind::OutOfBounds { address, len: 1 })?;
+
+        Ok(disasm::decode(bytes, address)?)
+    }
+
+    fn step(&self, cursor: usize, step: Step) -> Result<usize, ErrorKind> {
+        match step {
+            Step::Add(n) => {
+                let moved = if n < 0 {
+                    cursor.checked_sub(n.unsigned_abs())
+                } else {
+                    cursor.checked_add(n.unsigned_abs())
+                };
+
+                moved.ok_or(ErrorKind::Overflow { cursor })
+            }
+
+            Step::Deref => self
+                .read_u32(cursor)
+                .map(|value| value as usize)
+                .ok_or(ErrorKind::OutOfBounds { address: cursor, len: 4 }),
+
+            Step::Next => Ok(self.decode(cursor)?.next()),
+
+            Step::FollowRel32 => {
+                let instruction = self.decode(cursor)?;
+
+                match (instruction.relative, instruction.target()) {
+                    (Some(relative), Some(target)) if relative.size == 4 => Ok(target),
+
+                    _ => Err(ErrorKind::NotARelativeBranch {
+                        address: cursor,
+                        bytes: instruction.bytes().to_vec(),
+                    }),
+                }
+            }
+
+            Step::Displacement => operand(self.decode(cursor)?, |i| i.displacement, "displacement"),
+
+            Step::Immediate => operand(self.decode(cursor)?, |i| i.immediate, "immediate"),
+
+            Step::Expect(expected) => {
+                let found = self
+                    .read(cursor, expected.len())
+                    .ok_or(ErrorKind::OutOfBounds { address: cursor, len: expected.len() })?;
+
+                if found == expected {
+                    Ok(cursor)
+                } else {
+                    Err(ErrorKind::Unexpected { address: cursor, expected, found: found.to_vec() })
+                }
+            }
+        }
+    }
+
+    pub fn resolve(&self, recipe: &Recipe) -> Result<usize, Error> {
+        let slot = self.locate(recipe)?;
+        self.finish(recipe, slot)
+    }
+
+    // Runs the anchor and every step up to the trailing derefs.
+    // The result is where the value lives rather than the value, so it stays put between runs.
+    pub fn locate(&self, recipe: &Recipe) -> Result<usize, Error> {
+        let (locate, _) = recipe.split();
+
+        let cursor = self
+            .anchor(recipe.anchor)
+            .map_err(|kind| recipe.error(Location::Anchor(recipe.anchor.to_string()), kind))?;
+
+        self.run(recipe, cursor, locate, 0)
+    }
+
+    // Runs the trailing derefs from a slot that `locate` found.
+    pub fn finish(&self, recipe: &Recipe, slot: usize) -> Result<usize, Error> {
+        let (locate, finish) = recipe.split();
+        self.run(recipe, slot, finish, locate.len())
+    }
+
+    fn run(&self, recipe: &Recipe, mut cursor: usize, steps: &[Step], first_index: usize) -> Result<usize, Error> {
+        for (index, &step) in steps.iter().enumerate() {
+            cursor = self
+                .step(cursor, step)
+                .map_err(|kind| recipe.error(Location::Step(first_index + index, step), kind))?;
+        }
+
+        Ok(cursor)
+    }
+}
+
+// Moves onto the 32-bit operand that `field` picks out of `instruction`.
+fn operand(instruction: Instruction, field: impl Fn(&Instruction) -> Option<disasm::Field>,
+           name: &'static str) -> Result<usize, ErrorKind> {
+
+    match field(&instruction) {
+        Some(field) if field.size == 4 => Ok(instruction.address + field.offset),
+
+        _ => Err(ErrorKind::MissingOperand {
+            address: instruction.address,
+            operand: name,
+            bytes: instruction.bytes().to_vec(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Anchor, ErrorKind, Location, Memory, Recipe, Step};
+    use crate::disasm;
+    use crate::xref::Kind;
+
+    const BASE: usize = 0x0040_0000;
+
+    fn bytes() -> Vec<u8> {
+        let mut bytes = vec![
+            0x68, 0x80, 0x00, 0x40, 0x00,       // 00: push offset aAnchor
+            0xE8, 0x16, 0x00, 0x00, 0x00,       // 05: call 400020
+            0xE9, 0x21, 0x00, 0x00, 0x00,       // 0A: jmp 400030
+            0x0F, 0x84, 0x25, 0x00, 0x00, 0x00, // 0F: je 40003A
+            0x74, 0x02,                         // 15: je 400019
+            0x8B, 0x35, 0x60, 0x00, 0x40, 0x00, // 17: mov esi,dword ptr ds:[400060]
+            0xC3,                               // 1D: ret
+        ];
+
+        bytes.resize(0x60, 0xCC);
+        bytes.extend_from_slice(&0x1234_5678_u32.to_le_bytes());
+        bytes.resize(0x80, 0);
+        bytes.extend_from_slice(b"Anchor\0");
+        bytes
+    }
+
+    fn memory(bytes: &[u8]) -> Memory<'_> {
+        Memory::new(BASE, bytes).with_code(Some(BASE..BASE + 0x60))
+    }
+
+    #[test]
+    fn anchors() {
+        let bytes = bytes();
+        let memory = memory(&bytes);
+
+        assert_eq!(memory.anchor(Anchor::StringXref("Anchor", Kind::Push)).unwrap(), BASE);
+        assert_eq!(memory.anchor(Anchor::Signature("8B 35 ? ? 40 00")).unwrap(), BASE + 0x17);
+        assert_eq!(memory.anchor(Anchor::Address(BASE + 5)).unwrap(), BASE + 5);
+
+        assert!(matches!(memory.anchor(Anchor::StringXref("Missing", Kind::Push)), Err(ErrorKind::StringNotFound)));
+        assert!(matches!(
+            memory.anchor(Anchor::StringXref("Anchor", Kind::Mov)),
+            Err(ErrorKind::XrefNotFound { string, kind: Kind::Mov }) if string == BASE + 0x80
+        ));
+        assert!(matches!(memory.anchor(Anchor::Signature("90 90 90")), Err(ErrorKind::SignatureNotFound)));
+        assert!(matches!(memory.anchor(Anchor::Signature("8B 3")), Err(ErrorKind::Signature(_))));
+    }
+
+    #[test]
+    fn add() {
+        let bytes = bytes();
+        let memory = memory(&bytes);
+
+        assert_eq!(memory.step(BASE + 4, Step::Add(-4)).unwrap(), BASE);
+        assert_eq!(memory.step(BASE, Step::Add(0x17)).unwrap(), BASE + 0x17);
+        assert!(matches!(memory.step(3, Step::Add(-4)), Err(ErrorKind::Overflow { cursor: 3 })));
+        assert!(matches!(memory.step(usize::MAX, Step::Add(1)), Err(ErrorKind::Overflow { .. })));
+    }
+
+    #[test]
+    fn deref() {
+        let bytes = bytes();
+        let memory = memory(&bytes);
+
+        assert_eq!(memory.step(BASE + 0x60, Step::Deref).unwrap(), 0x1234_5678);
+        assert_eq!(memory.step(BASE + 1, Step::Deref).unwrap(), BASE + 0x80);
+
+        let end = BASE + bytes.len() - 2;
+        assert!(matches!(memory.step(end, Step::Deref), Err(ErrorKind::OutOfBounds { address, len: 4 }) if address == end));
+        assert!(matches!(memory.step(BASE - 4, Step::Deref), Err(ErrorKind::OutOfBounds { .. })));
+    }
+
+    #[test]
+    fn next() {
+        let bytes = bytes();
+        let memory = memory(&bytes);
+
+        assert_eq!(memory.step(BASE, Step::Next).unwrap(), BASE + 5);
+        assert_eq!(memory.step(BASE + 0xF, Step::Next).unwrap(), BASE + 0x15);
+        assert_eq!(memory.step(BASE + 0x17, Step::Next).unwrap(), BASE + 0x1D);
+        assert!(matches!(memory.step(BASE + bytes.len(), Step::Next), Err(ErrorKind::OutOfBounds { .. })));
+
+        // The nul at the end of the string starts an `add r/m8, r8` that has no ModRM byte.
+        let nul = BASE + bytes.len() - 1;
+        assert!(matches!(memory.step(nul, Step::Next), Err(ErrorKind::Decode(disasm::Error::Truncated(address))) if address == nul));
+    }
+
+    #[test]
+    fn follow_rel32() {
+        let bytes = bytes();
+        let memory = memory(&bytes);
+
+        assert_eq!(memory.step(BASE + 5, Step::FollowRel32).unwrap(), BASE + 0x20);
+        assert_eq!(memory.step(BASE + 0xA, Step::FollowRel32).unwrap(), BASE + 0x30);
+        assert_eq!(memory.step(BASE + 0xF, Step::FollowRel32).unwrap(), BASE + 0x3A);
+
+        // A rel8 jcc isn't a rel32 branch, and neither is a push.
+        assert!(matches!(
+            memory.step(BASE + 0x15, Step::FollowRel32),
+            Err(ErrorKind::NotARelativeBranch { bytes, .. }) if bytes == [0x74, 0x02]
+        ));
+        assert!(matches!(memory.step(BASE, Step::FollowRel32), Err(ErrorKind::NotARelativeBranch { .. })));
+    }
+
+    #[test]
+    fn operands() {
+        let bytes = bytes();
+        let memory = memory(&bytes);
+
+        assert_eq!(memory.step(BASE, Step::Immediate).unwrap(), BASE + 1);
+        assert_eq!(memory.step(BASE + 0x17, Step::Displacement).unwrap(), BASE + 0x19);
+
+        assert!(matches!(
+            memory.step(BASE, Step::Displacement),
+            Err(ErrorKind::MissingOperand { operand: "displacement", .. })
+        ));
+        assert!(matches!(
+            memory.step(BASE + 0x17, Step::Immediate),
+            Err(ErrorKind::MissingOperand { operand: "immediate", .. })
+        ));
+    }
+
+    #[test]
+    fn expect() {
+        let bytes = bytes();
+        let memory = memory(&bytes);
+
+        assert_eq!(memory.step(BASE + 0x17, Step::Expect(&[0x8B, 0x35])).unwrap(), BASE + 0x17);
+        assert!(matches!(
+            memory.step(BASE + 0x17, Step::Expect(&[0x8B, 0x3D])),
+            Err(ErrorKind::Unexpected { found, .. }) if found == [0x8B, 0x35]
+        ));
+        assert!(matches!(
+            memory.step(BASE + bytes.len() - 1, Step::Expect(&[0x00, 0x00])),
+            Err(ErrorKind::OutOfBounds { len: 2, .. })
+        ));
+    }
+
+    // The trailing derefs read the slot, so `locate` stops before them and `finish` runs them.
+    #[test]
+    fn locate_stops_before_the_trailing_derefs() {
+        let bytes = bytes();
+        let memory = memory(&bytes);
+
+        let recipe = Recipe {
+            name: "DATA",
+            anchor: Anchor::Signature("8B 35"),
+            steps: &[Step::Displacement, Step::Deref, Step::Deref],
+        };
+
+        let slot = memory.locate(&recipe).unwrap();
+        assert_eq!(slot, BASE + 0x19);
+        assert_eq!(memory.finish(&recipe, slot).unwrap(), 0x1234_5678);
+        assert_eq!(memory.resolve(&recipe).unwrap(), 0x1234_5678);
+    }
+
+    #[test]
+    fn errors_name_the_failing_step() {
+        let bytes = bytes();
+        let memory = memory(&bytes);
+
+        let recipe = Recipe {
+            name: "TEST",
+            anchor: Anchor::StringXref("Anchor", Kind::Push),
+            steps: &[Step::Next, Step::Expect(&[0xE8]), Step::Next, Step::Expect(&[0x8B, 0x35]), Step::Deref],
+        };
+
+        let error = memory.resolve(&recipe).unwrap_err();
+        assert_eq!(error.name, "TEST");
+        assert!(matches!(error.location, Location::Step(3, Step::Expect(&[0x8B, 0x35]))));
+        assert_eq!(
+            error.to_string(),
+            "failed to resolve TEST at step 3 (expect 8B 35): expected [8B, 35] at 0x40000a but found [E9, 21]",
+        );
+
+        let recipe = Recipe { anchor: Anchor::StringXref("Missing", Kind::Push), ..recipe };
+        let error = memory.resolve(&recipe).unwrap_err();
+        assert_eq!(error.to_string(), "failed to resolve TEST at anchor (push imm32 \"Missing\"): the string was not found");
+    }
+}
@@ -0,0 +1,267 @@
+use crate::signature::Signature;
+
+use memchr::{memchr2_iter, memchr3_iter, memchr_iter};
+
+// Bytes that show up the most in 32-bit MSVC code and data, most common first.
+// We anchor each signature on its fixed byte that ranks lowest in this list so that
+// the scan loop rarely has to verify a candidate.
+const COMMON_BYTES: [u8; 32] = [
+    0x00, 0xFF, 0x8B, 0xCC, 0x24, 0x89, 0x44, 0x45, 0x04, 0x08, 0x0F, 0x85, 0x83, 0xE8, 0x01, 0x10,
+    0x84, 0x74, 0x50, 0x56, 0x0C, 0x75, 0x4D, 0x40, 0x8D, 0x33, 0x3B, 0x55, 0x68, 0x5E, 0xC3, 0xC0,
+];
+
+fn commonness(byte: u8) -> usize {
+    COMMON_BYTES
+        .iter()
+        .position(|&common| common == byte)
+        .map_or(0, |rank| COMMON_BYTES.len() - rank)
+}
+
+#[derive(Clone, Copy)]
+struct Anchor {
+    signature: usize,
+
+    // Where the anchor byte sits inside its signature.
+    index: usize,
+}
+
+// Finds every match of many signatures in one pass over the memory.
+pub struct Scanner<'s> {
+    signatures: Vec<&'s Signature>,
+
+    // For each byte value, the signatures that are anchored on that byte.
+    anchors: Vec<Vec<Anchor>>,
+
+    // Signatures without a single fully-fixed byte. We verify these at every position.
+    unanchored: Vec<usize>,
+
+    // The distinct anchor bytes. When there are few of them and nothing is unanchored,
+    // memchr skips to the candidates instead of us looking at every byte.
+    anchor_bytes: Vec<u8>,
+}
+
+impl<'s> Scanner<'s> {
+    pub fn new(signatures: &[&'s Signature]) -> Scanner<'s> {
+        let mut anchors = vec![vec![]; 256];
+        let mut unanchored = vec![];
+
+        for (signature, pattern) in signatures.iter().enumerate() {
+            // An empty signature matches nothing, like `Signature::scan`.
+            if pattern.is_empty() {
+                continue;
+            }
+
+            let rarest = (0..pattern.len())
+                .filter_map(|index| pattern.fixed_byte(index).map(|byte| (index, byte)))
+                .min_by_key(|&(_, byte)| commonness(byte));
+
+            if let Some((index, byte)) = rarest {
+                anchors[usize::from(byte)].push(Anchor { signature, index });
+            } else {
+                unanchored.push(signature);
+            }
+        }
+
+        #[allow(clippy::cast_possible_truncation)]
+        let anchor_bytes = (0..anchors.len())
+            .filter(|&byte| !anchors[byte].is_empty())
+            .map(|byte| byte as u8)
+            .collect();
+
+        Scanner {
+            signatures: signatures.to_vec(),
+            anchors,
+            unanchored,
+            anchor_bytes,
+        }
+    }
+
+    // Returns, for each signature, the cursor offsets of all its matches in ascending order.
+    pub fn scan(&self, memory: &[u8]) -> Vec<Vec<usize>> {
+        let mut matches = vec![vec![]; self.signatures.len()];
+
+        self.each_match(memory, |signature, cursor| {
+            matches[signature].push(cursor);
+            true
+        });
+
+        matches
+    }
+
+    // Returns, for each signature, the cursor offset of its first match.
+    pub fn first(&self, memory: &[u8]) -> Vec<Option<usize>> {
+        let mut matches = vec![None; self.signatures.len()];
+        let mut remaining = self.signatures.len();
+
+        self.each_match(memory, |signature, cursor| {
+            let slot = &mut matches[signature];
+
+            if slot.is_none() {
+                *slot = Some(cursor);
+                remaining -= 1;
+            }
+
+            remaining > 0
+        });
+
+        matches
+    }
+
+    // Calls `on_match` with the signature index and cursor offset of each match
+    // until `on_match` returns false.
+    fn each_match(&self, memory: &[u8], on_match: impl FnMut(usize, usize) -> bool) {
+        if self.signatures.is_empty() {
+            return;
+        }
+
+        if self.unanchored.is_empty() {
+            match *self.anchor_bytes.as_slice() {
+                [] => {}
+                [a] => self.each_candidate(memory, memchr_iter(a, memory), on_match),
+                [a, b] => self.each_candidate(memory, memchr2_iter(a, b, memory), on_match),
+                [a, b, c] => self.each_candidate(memory, memchr3_iter(a, b, c, memory), on_match),
+                _ => self.each_candidate(memory, 0..memory.len(), on_match),
+            }
+        } else {
+            self.each_candidate(memory, 0..memory.len(), on_match);
+        }
+    }
+
+    // Verifies the signatures that could match at each position in `positions`, which must ascend.
+    fn each_candidate(&self, memory: &[u8], positions: impl Iterator<Item = usize>,
+                      mut on_match: impl FnMut(usize, usize) -> bool) {
+
+        for position in positions {
+            for &signature in &self.unanchored {
+                let pattern = self.signatures[signature];
+
+                if pattern.matches(&memory[position..]) && !on_match(signature, position + pattern.offset()) {
+                    return;
+                }
+            }
+
+            for anchor in &self.anchors[usize::from(memory[position])] {
+                let Some(start) = position.checked_sub(anchor.index) else {
+                    continue;
+                };
+
+                let pattern = self.signatures[anchor.signature];
+
+                if pattern.matches(&memory[start..]) && !on_match(anchor.signature, start + pattern.offset()) {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Scanner;
+    use crate::signature::Signature;
+
+    // xorshift32, so that a failure reproduces.
+    struct Rng(u32);
+
+    impl Rng {
+        fn next(&mut self) -> u32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 17;
+            self.0 ^= self.0 << 5;
+            self.0
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            self.next() as usize % n
+        }
+    }
+
+    // Few distinct byte values, so that signatures match more than once.
+    fn memory(rng: &mut Rng, len: usize) -> Vec<u8> {
+        (0..len).map(|_| [0x00, 0x8B, 0xE8, 0x68, 0xC3, 0x90][rng.below(6)] ^ u8::from(rng.below(8) == 0)).collect()
+    }
+
+    // A piece of `memory` with some bytes and nibbles turned into wildcards.
+    fn signature(rng: &mut Rng, memory: &[u8]) -> Signature {
+        let len = 1 + rng.below(6);
+        let start = rng.below(memory.len() - len);
+
+        let mut text = memory[start..start + len]
+            .iter()
+            .map(|byte| match rng.below(6) {
+                0 => String::from("??"),
+                1 => format!("{:X}?", byte >> 4),
+                2 => format!("?{:X}", byte & 0xF),
+                _ => format!("{byte:02X}"),
+            })
+            .collect::<Vec<_>>();
+
+        if rng.below(2) == 0 {
+            text.insert(rng.below(len + 1), String::from("|"));
+        }
+
+        Signature::parse(&text.join(" ")).unwrap_or_else(|_| Signature::from_bytes(&memory[start..=start]))
+    }
+
+    fn naive(signature: &Signature, memory: &[u8]) -> Vec<usize> {
+        if signature.is_empty() {
+            return vec![];
+        }
+
+        (0..memory.len())
+            .filter(|&start| signature.matches(&memory[start..]))
+            .map(|start| start + signature.offset())
+            .collect()
+    }
+
+    fn check(signatures: &[Signature], memory: &[u8]) {
+        let references = signatures.iter().collect::<Vec<_>>();
+        let scanner = Scanner::new(&references);
+        let expected = signatures.iter().map(|signature| naive(signature, memory)).collect::<Vec<_>>();
+
+        assert_eq!(scanner.scan(memory), expected, "{signatures:?}");
+
+        let first = expected.iter().map(|matches| matches.first().copied()).collect::<Vec<_>>();
+        assert_eq!(scanner.first(memory), first, "{signatures:?}");
+
+        for (signature, first) in signatures.iter().zip(first) {
+            assert_eq!(signature.scan(memory), first, "{signature}");
+        }
+    }
+
+    #[test]
+    fn matches_the_naive_scan() {
+        let mut rng = Rng(0x1234_5678);
+
+        for _ in 0..500 {
+            let len = 64 + rng.below(512);
+            let memory = memory(&mut rng, len);
+
+            // One to three anchor bytes go through memchr; more go byte by byte.
+            let count = 1 + rng.below(6);
+            let signatures = (0..count).map(|_| signature(&mut rng, &memory)).collect::<Vec<_>>();
+
+            check(&signatures, &memory);
+        }
+    }
+
+    #[test]
+    fn matches_the_naive_scan_at_the_edges() {
+        let memory = [0x8B, 0x35, 0x00, 0x8B, 0x35, 0x00, 0x8B];
+
+        let signatures = [
+            Signature::parse("8B").unwrap(),
+            Signature::parse("8B 35 00 8B 35 00 8B").unwrap(),
+            Signature::parse("8B 35 00 8B 35 00 8B 35").unwrap(),
+            Signature::parse("?? | 35").unwrap(),
+            Signature::parse("8? ?5 |").unwrap(),
+            Signature::from_bytes(&[]),
+        ];
+
+        check(&signatures, &memory);
+        check(&signatures[..1], &memory);
+        check(&signatures[3..], &memory);
+        check(&signatures, &[]);
+        check(&[], &memory);
+    }
+}
@@ -0,0 +1,275 @@
+use std::fmt;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum Error {
+    #[error("the signature is empty")]
+    Empty,
+
+    #[error("invalid token \"{token}\" at column {column}; expected a hex byte (\"8B\"), \
+             a wildcard (\"?\" or \"??\"), a nibble wildcard (\"8?\" or \"?B\"), or a cursor (\"|\")")]
+    InvalidToken {
+        token: String,
+        column: usize,
+    },
+
+    #[error("a second cursor at column {column}; the first cursor is at column {first}")]
+    DuplicateCursor {
+        column: usize,
+        first: usize,
+    },
+
+    #[error("the signature only has wildcards, so it would match any address")]
+    OnlyWildcards,
+}
+
+// A byte pattern in the format that x64dbg and IDA use:
+//
+//      8B 35 ?? ?? ?? ?? 56
+//      E8 | ? ? ? ? 8? 4?
+//
+// `?` and `??` match any byte, `8?` and `?B` match half a byte,
+// and `|` marks the offset we want back from a scan.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Signature {
+    // Each pattern byte with its wildcard bits already cleared.
+    values: Vec<u8>,
+
+    // The bits of each pattern byte that must match: 0xFF, 0xF0, 0x0F, or 0x00.
+    masks: Vec<u8>,
+
+    // Where the cursor sits relative to the start of a match.
+    offset: usize,
+}
+
+impl Signature {
+    pub fn parse(signature: &str) -> Result<Signature, Error> {
+        let mut values = vec![];
+        let mut masks = vec![];
+        let mut cursor: Option<(usize, usize)> = None;
+
+        for (column, token) in tokens(signature) {
+            if token == "|" {
+                if let Some((_, first)) = cursor {
+                    return Err(Error::DuplicateCursor { column, first });
+                }
+
+                cursor = Some((values.len(), column));
+                continue;
+            }
+
+            let (value, mask) = parse_byte(token)
+                .ok_or_else(|| Error::InvalidToken { token: String::from(token), column })?;
+
+            values.push(value);
+            masks.push(mask);
+        }
+
+        if values.is_empty() {
+            return Err(Error::Empty);
+        }
+
+        if masks.iter().all(|&mask| mask == 0) {
+            return Err(Error::OnlyWildcards);
+        }
+
+        Ok(Signature {
+            values,
+            masks,
+            offset: cursor.map_or(0, |(offset, _)| offset),
+        })
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Signature {
+        Signature {
+            values: bytes.to_vec(),
+            masks: vec![0xFF; bytes.len()],
+            offset: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    // The pattern byte at `index` if all eight of its bits are fixed.
+    pub fn fixed_byte(&self, index: usize) -> Option<u8> {
+        if self.masks[index] == 0xFF {
+            Some(self.values[index])
+        } else {
+            None
+        }
+    }
+
+    // Whether `window` starts with bytes that match this signature.
+    pub fn matches(&self, window: &[u8]) -> bool {
+        window.len() >= self.len() &&
+        self.values
+            .iter()
+            .zip(self.masks.iter())
+            .zip(window.iter())
+            .all(|((value, mask), byte)| byte & mask == *value)
+    }
+
+    // Returns the index of the cursor in `memory` for the first match.
+    // An empty signature from `from_bytes` matches nothing.
+    pub fn scan(&self, memory: &[u8]) -> Option<usize> {
+        if self.is_empty() {
+            return None;
+        }
+
+        memory
+            .windows(self.len())
+            .position(|window| self.matches(window))
+            .map(|start| start + self.offset)
+    }
+}
+
+impl FromStr for Signature {
+    type Err = Error;
+
+    fn from_str(signature: &str) -> Result<Signature, Error> {
+        Signature::parse(signature)
+    }
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const HEX: &[u8; 16] = b"0123456789ABCDEF";
+
+        for (index, (value, mask)) in self.values.iter().zip(self.masks.iter()).enumerate() {
+            if index > 0 {
+                f.write_str(" ")?;
+            }
+
+            if index == self.offset && self.offset > 0 {
+                f.write_str("| ")?;
+            }
+
+            let nibble = |mask: u8, value: u8| if mask == 0 { '?' } else { char::from(HEX[usize::from(value)]) };
+            write!(f, "{}{}", nibble(mask >> 4, value >> 4), nibble(mask & 0x0F, value & 0x0F))?;
+        }
+
+        if self.offset == self.len() {
+            f.write_str(" |")?;
+        }
+
+        Ok(())
+    }
+}
+
+// Splits a signature into whitespace-separated tokens and their columns.
+// A cursor is its own token even if it touches a byte, as in "E8|?? ?? ?? ??".
+fn tokens(signature: &str) -> impl Iterator<Item = (usize, &str)> {
+    let mut rest = signature.char_indices().peekable();
+
+    std::iter::from_fn(move || {
+        while let Some(&(_, c)) = rest.peek() {
+            if c.is_whitespace() {
+                rest.next();
+            } else {
+                break;
+            }
+        }
+
+        let (start, first) = rest.next()?;
+
+        if first == '|' {
+            return Some((start, &signature[start..=start]));
+        }
+
+        let mut end = start + first.len_utf8();
+
+        while let Some(&(index, c)) = rest.peek() {
+            if c.is_whitespace() || c == '|' {
+                break;
+            }
+
+            end = index + c.len_utf8();
+            rest.next();
+        }
+
+        Some((start, &signature[start..end]))
+    })
+}
+
+// Parses "8B", "??", "?", "8?", or "?B" into a (value, mask) pair.
+fn parse_byte(token: &str) -> Option<(u8, u8)> {
+    fn nibble(c: u8) -> Option<(u8, u8)> {
+        if c == b'?' {
+            Some((0, 0))
+        } else {
+            let value = char::from(c).to_digit(16)?;
+
+            #[allow(clippy::cast_possible_truncation)]
+            Some((value as u8, 0xF))
+        }
+    }
+
+    match *token.as_bytes() {
+        [b'?'] => Some((0, 0)),
+
+        [high, low] => {
+            let (high_value, high_mask) = nibble(high)?;
+            let (low_value, low_mask) = nibble(low)?;
+            Some((high_value << 4 | low_value, high_mask << 4 | low_mask))
+        }
+
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Error, Signature};
+
+    #[test]
+    fn parse_rejects_bad_signatures() {
+        assert_eq!(Signature::parse(""), Err(Error::Empty));
+        assert_eq!(Signature::parse(" | "), Err(Error::Empty));
+        assert_eq!(Signature::parse("?? ? ??"), Err(Error::OnlyWildcards));
+        assert_eq!(Signature::parse("8B | 35 |"), Err(Error::DuplicateCursor { column: 8, first: 3 }));
+        assert_eq!(Signature::parse("8B 3G"), Err(Error::InvalidToken { token: String::from("3G"), column: 3 }));
+        assert_eq!(Signature::parse("8B 356"), Err(Error::InvalidToken { token: String::from("356"), column: 3 }));
+    }
+
+    #[test]
+    fn parse_round_trips_through_display() {
+        for signature in &["8B 35 ?? ?? ?? ?? 56", "E8 | ?? ?? ?? ?? 8? ?B", "68 ?? |"] {
+            assert_eq!(Signature::parse(signature).unwrap().to_string(), *signature);
+        }
+
+        assert_eq!(Signature::parse("E8|? ? 8?").unwrap().to_string(), "E8 | ?? ?? 8?");
+    }
+
+    #[test]
+    fn scan_finds_the_cursor_of_the_first_match() {
+        let memory = [0x90, 0x8B, 0x35, 0x01, 0x02, 0x8B, 0x35, 0x03, 0x04];
+
+        assert_eq!(Signature::parse("8B 35").unwrap().scan(&memory), Some(1));
+        assert_eq!(Signature::parse("8B | 35 ?? 04").unwrap().scan(&memory), Some(6));
+        assert_eq!(Signature::parse("?5 0?").unwrap().scan(&memory), Some(2));
+        assert_eq!(Signature::parse("35 ?? ?? ?? ?? ?? ?? ??").unwrap().scan(&memory), None);
+        assert_eq!(Signature::parse("8B 36").unwrap().scan(&memory), None);
+    }
+
+    #[test]
+    fn scan_with_an_empty_signature_finds_nothing() {
+        let empty = Signature::from_bytes(&[]);
+
+        assert!(empty.is_empty());
+        assert_eq!(empty.scan(&[0x90, 0x90]), None);
+        assert_eq!(empty.scan(&[]), None);
+        assert_eq!(Signature::from_bytes(&[0x90]).scan(&[]), None);
+    }
+}
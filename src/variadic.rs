@@ -0,0 +1,87 @@
+// Calls C functions that take `...`.
+// Rust can't forward variadic arguments, so variadic functions!/vtable! entries take them as one tuple:
+//
+//      engine.con_printf(b"%s has %d players\n\0".as_ptr(), (name.as_ptr(), count));
+
+use std::mem;
+
+// A type that C passes through `...` as is.
+// C promotes smaller integers and f32, so the caller widens those to i32 and f64.
+// An impl promises that the type is one of those that C reads back from `...` unchanged.
+#[allow(clippy::missing_safety_doc)]
+pub unsafe trait VarArg {}
+
+macro_rules! var_arg {
+    ($($ty:ty),*) => {
+        $(unsafe impl VarArg for $ty {})*
+    };
+}
+
+var_arg!(i32, u32, i64, u64, isize, usize, f64);
+
+unsafe impl<T> VarArg for *const T {}
+unsafe impl<T> VarArg for *mut T {}
+
+// A tuple of variadic arguments, which we pass after the `Fixed` tuple of declared arguments.
+pub trait VarArgs<Fixed, R> {
+    unsafe fn call(self, address: usize, fixed: Fixed) -> R;
+}
+
+macro_rules! var_args_impl {
+    ([$($rest:ident $rest_value:ident)*] [$($fixed:ident $fixed_value:ident)+]) => {
+        impl<R, $($fixed,)+ $($rest: VarArg,)*> VarArgs<($($fixed,)+), R> for ($($rest,)*) {
+            unsafe fn call(self, address: usize, ($($fixed_value,)+): ($($fixed,)+)) -> R {
+                let function = mem::transmute::<usize, unsafe extern "C" fn($($fixed,)+ ...) -> R>(address);
+                let ($($rest_value,)*) = self;
+                function($($fixed_value,)+ $($rest_value,)*)
+            }
+        }
+    };
+}
+
+// Implements VarArgs for every tuple of up to eight variadic arguments after one to four declared arguments.
+macro_rules! var_args {
+    ([$($rest:tt)*]) => {
+        var_args_impl!([$($rest)*] [F0 f0]);
+        var_args_impl!([$($rest)*] [F0 f0 F1 f1]);
+        var_args_impl!([$($rest)*] [F0 f0 F1 f1 F2 f2]);
+        var_args_impl!([$($rest)*] [F0 f0 F1 f1 F2 f2 F3 f3]);
+    };
+
+    ([$($rest:tt)*] $next:ident $next_value:ident $($more:tt)*) => {
+        var_args!([$($rest)*]);
+        var_args!([$($rest)* $next $next_value] $($more)*);
+    };
+}
+
+var_args!([] V0 v0 V1 v1 V2 v2 V3 v3 V4 v4 V5 v5 V6 v6 V7 v7);
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::VarArgs;
+    use std::ffi::CStr;
+    use std::os::raw::c_char;
+
+    fn snprintf<V: VarArgs<(*mut c_char, usize, *const c_char), i32>>(format: &[u8], rest: V) -> String {
+        let mut buffer = [0 as c_char; 64];
+        let address = libc::snprintf as *const () as usize;
+
+        let written = unsafe { rest.call(address, (buffer.as_mut_ptr(), buffer.len(), format.as_ptr().cast())) };
+        assert!(written >= 0);
+
+        unsafe { CStr::from_ptr(buffer.as_ptr()) }.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn passes_each_argument() {
+        assert_eq!(snprintf(b"none\0", ()), "none");
+        assert_eq!(snprintf(b"%d\0", (-7,)), "-7");
+        assert_eq!(snprintf(b"%s=%u\0", (b"players\0".as_ptr(), 32_u32)), "players=32");
+        assert_eq!(snprintf(b"%.2f %lld\0", (1.5_f64, i64::MIN)), "1.50 -9223372036854775808");
+
+        assert_eq!(
+            snprintf(b"%d%d%d%d%d%d%d%d\0", (1, 2, 3, 4, 5, 6, 7, 8)),
+            "12345678",
+        );
+    }
+}
@@ -0,0 +1,219 @@
+use crate::memory::{self, PatchSet};
+
+use std::any;
+use std::marker::PhantomData;
+use std::mem::{self, ManuallyDrop};
+use std::ptr;
+
+use log::error;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("{0} has a null vtable")]
+    NullVtable(&'static str),
+
+    #[error("{interface} has {len} vtable entries, so it has no slot {index}")]
+    SlotOutOfRange {
+        interface: &'static str,
+        index: usize,
+        len: usize,
+    },
+
+    #[error("patch error: {0}")]
+    Patch(#[from] memory::Error),
+}
+
+// A vtable index and the type of the function that lives there.
+//
+//      type PaintTraverse = unsafe extern "fastcall" fn(*const Panel, usize, *const Panel, bool, bool);
+//      const PAINT_TRAVERSE: Slot<PaintTraverse> = Slot::new(41);
+pub struct Slot<F> {
+    index: usize,
+    function: PhantomData<F>,
+}
+
+impl<F> Slot<F> {
+    pub const fn new(index: usize) -> Slot<F> {
+        Slot {
+            index,
+            function: PhantomData,
+        }
+    }
+
+    pub const fn index(self) -> usize {
+        self.index
+    }
+}
+
+impl<F> Clone for Slot<F> {
+    fn clone(&self) -> Slot<F> {
+        *self
+    }
+}
+
+impl<F> Copy for Slot<F> {}
+
+// Points an interface at our copy of its vtable, so that we can replace some of its entries.
+// The interface must start with a pointer to its vtable of `N` entries.
+//
+//      let mut hook = VtableHook::<Panel, 60>::new(panel)?;
+//      hook.hook(PAINT_TRAVERSE, my_paint_traverse)?;
+//      OLD_PAINT_TRAVERSE = Some(hook.original(PAINT_TRAVERSE)?);
+//      hook.enable()?;
+pub struct VtableHook<I, const N: usize> {
+    original: *mut [usize; N],
+    shadow: ManuallyDrop<Box<[usize; N]>>,
+    patch: PatchSet,
+    interface: PhantomData<*mut I>,
+}
+
+impl<I, const N: usize> VtableHook<I, N> {
+    pub unsafe fn new(interface: *mut I) -> Result<VtableHook<I, N>, Error> {
+        memory::ptr_check(interface)?;
+
+        let original = *interface.cast::<*mut [usize; N]>();
+
+        if original.is_null() {
+            return Err(Error::NullVtable(any::type_name::<I>()));
+        }
+
+        let mut shadow = ManuallyDrop::new(Box::new(*original));
+        let mut patch = PatchSet::new();
+        patch.value(interface.cast::<*mut [usize; N]>(), ptr::from_mut(shadow.as_mut()))?;
+
+        Ok(VtableHook {
+            original,
+            shadow,
+            patch,
+            interface: PhantomData,
+        })
+    }
+
+    // Replaces the entry at `slot` with `function`.
+    pub fn hook<F: Copy>(&mut self, slot: Slot<F>, function: F) -> Result<&mut VtableHook<I, N>, Error> {
+        let entry = self.shadow.get_mut(slot.index).ok_or_else(|| out_of_range::<I>(slot.index, N))?;
+        *entry = unsafe { to_entry(function) };
+        Ok(self)
+    }
+
+    // The function that the game's own vtable has at `slot`.
+    pub fn original<F: Copy>(&self, slot: Slot<F>) -> Result<F, Error> {
+        let entry = unsafe { (*self.original).get(slot.index) }.ok_or_else(|| out_of_range::<I>(slot.index, N))?;
+        Ok(unsafe { from_entry(*entry) })
+    }
+
+    pub unsafe fn enable(&mut self) -> Result<(), Error> {
+        Ok(self.patch.apply()?)
+    }
+
+    pub unsafe fn disable(&mut self) -> Result<(), Error> {
+        Ok(self.patch.restore()?)
+    }
+}
+
+impl<I, const N: usize> Drop for VtableHook<I, N> {
+    fn drop(&mut self) {
+        unsafe {
+            if let Err(e) = self.disable() {
+                // Someone else owns the vtable pointer now and may still point it at our vtable,
+                // so leak our vtable rather than free it out from under them.
+                error!("Failed to restore the {} vtable: {}", any::type_name::<I>(), e);
+            } else {
+                ManuallyDrop::drop(&mut self.shadow);
+            }
+        }
+    }
+}
+
+fn out_of_range<I>(index: usize, len: usize) -> Error {
+    Error::SlotOutOfRange {
+        interface: any::type_name::<I>(),
+        index,
+        len,
+    }
+}
+
+// A vtable entry is a function pointer, so `F` must be a function pointer type.
+unsafe fn to_entry<F: Copy>(function: F) -> usize {
+    assert_eq!(mem::size_of::<F>(), mem::size_of::<usize>());
+    mem::transmute_copy(&function)
+}
+
+unsafe fn from_entry<F: Copy>(entry: usize) -> F {
+    assert_eq!(mem::size_of::<F>(), mem::size_of::<usize>());
+    mem::transmute_copy(&entry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Error, Slot, VtableHook};
+
+    use std::ptr;
+
+    type GetValue = unsafe extern "C" fn(this: *const Interface) -> u32;
+    const GET_VALUE: Slot<GetValue> = Slot::new(1);
+    const PAST_THE_END: Slot<GetValue> = Slot::new(3);
+
+    #[repr(C)]
+    struct Interface {
+        vtable: *const [usize; 3],
+        value: u32,
+    }
+
+    unsafe extern "C" fn get_value(this: *const Interface) -> u32 {
+        (*this).value
+    }
+
+    unsafe extern "C" fn get_double_value(this: *const Interface) -> u32 {
+        (*this).value * 2
+    }
+
+    unsafe fn call(interface: *const Interface, slot: Slot<GetValue>) -> u32 {
+        let function: GetValue = std::mem::transmute((*(*interface).vtable)[slot.index()]);
+        function(interface)
+    }
+
+    #[test]
+    fn hooks_and_restores_a_slot() {
+        let vtable = [0, get_value as GetValue as usize, 0];
+        let mut interface = Interface { vtable: ptr::from_ref(&vtable), value: 21 };
+        let interface = ptr::from_mut(&mut interface);
+
+        unsafe {
+            let mut hook = VtableHook::<Interface, 3>::new(interface).unwrap();
+            hook.hook(GET_VALUE, get_double_value).unwrap();
+            assert_eq!(call(interface, GET_VALUE), 21);
+
+            hook.enable().unwrap();
+            assert_eq!(call(interface, GET_VALUE), 42);
+            assert_eq!(hook.original(GET_VALUE).unwrap()(interface), 21);
+
+            // The game's own vtable stays untouched.
+            assert_eq!(vtable[1], get_value as GetValue as usize);
+
+            drop(hook);
+            assert_eq!((*interface).vtable, ptr::from_ref(&vtable));
+            assert_eq!(call(interface, GET_VALUE), 21);
+        }
+    }
+
+    #[test]
+    fn slots_past_the_end_are_errors() {
+        let vtable = [0, get_value as GetValue as usize, 0];
+        let mut interface = Interface { vtable: ptr::from_ref(&vtable), value: 21 };
+
+        unsafe {
+            let mut hook = VtableHook::<Interface, 3>::new(ptr::from_mut(&mut interface)).unwrap();
+
+            assert!(matches!(hook.hook(PAST_THE_END, get_double_value), Err(Error::SlotOutOfRange { index: 3, len: 3, .. })));
+            assert!(matches!(hook.original(PAST_THE_END), Err(Error::SlotOutOfRange { index: 3, len: 3, .. })));
+        }
+    }
+
+    #[test]
+    fn null_vtable_is_an_error() {
+        let mut interface = Interface { vtable: ptr::null(), value: 0 };
+        assert!(matches!(unsafe { VtableHook::<Interface, 3>::new(ptr::from_mut(&mut interface)) }, Err(Error::NullVtable(_))));
+    }
+}
@@ -0,0 +1,219 @@
+use crate::resolver::Memory;
+use crate::scanner::Scanner;
+use crate::signature::Signature;
+
+use std::convert::TryInto;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    // 68 imm32: push imm32
+    Push,
+
+    // B8+r imm32: mov r32, imm32
+    Mov,
+
+    // E8 rel32: call rel32
+    Call,
+
+    // E9 rel32: jmp rel32
+    Jmp,
+
+    // The address shows up in code, but not as the operand of a push or mov, e.g. `mov esi,[imm32]`.
+    Operand,
+
+    // The address is stored in data, e.g. in a vtable or a table of strings.
+    Pointer,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Kind::Push => "push imm32",
+            Kind::Mov => "mov r32, imm32",
+            Kind::Call => "call rel32",
+            Kind::Jmp => "jmp rel32",
+            Kind::Operand => "operand",
+            Kind::Pointer => "pointer",
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Xref {
+    pub kind: Kind,
+
+    // The address of the instruction, or of the pointer slot for `Kind::Pointer` and `Kind::Operand`.
+    pub address: usize,
+}
+
+impl fmt::Display for Xref {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {:#x}", self.kind, self.address)
+    }
+}
+
+// Finds every reference to `target` in `memory`, ordered by address.
+// We match bytes rather than decode instructions, so a reference may be a false positive
+// when an unrelated instruction happens to contain the same bytes.
+pub fn find(memory: &Memory, target: usize) -> Vec<Xref> {
+    const PUSH: u8 = 0x68;
+    const MOV_EAX: u8 = 0xB8;
+    const MOV_EDI: u8 = 0xBF;
+    const CALL_REL32: u8 = 0xE8;
+    const JMP_REL32: u8 = 0xE9;
+
+    let bytes = memory.bytes();
+    let base = memory.base();
+    let mut xrefs = vec![];
+
+    #[allow(clippy::cast_possible_truncation)]
+    let target_u32 = target as u32;
+
+    let absolute = Signature::from_bytes(&target_u32.to_le_bytes());
+
+    for offset in Scanner::new(&[&absolute]).scan(bytes).remove(0) {
+        let in_code = memory.is_code(offset);
+
+        let xref = match offset.checked_sub(1).map(|opcode| (opcode, bytes[opcode])) {
+            Some((opcode, PUSH)) if in_code => Xref { kind: Kind::Push, address: base + opcode },
+            Some((opcode, MOV_EAX..=MOV_EDI)) if in_code => Xref { kind: Kind::Mov, address: base + opcode },
+            _ if in_code => Xref { kind: Kind::Operand, address: base + offset },
+            _ => Xref { kind: Kind::Pointer, address: base + offset },
+        };
+
+        xrefs.push(xref);
+    }
+
+    for code in memory.code() {
+        for offset in code.clone() {
+            let kind = match bytes[offset] {
+                CALL_REL32 => Kind::Call,
+                JMP_REL32 => Kind::Jmp,
+                _ => continue,
+            };
+
+            let operand = match bytes.get(offset + 1..offset + 5) {
+                Some(operand) => u32::from_le_bytes(operand.try_into().unwrap()),
+                None => continue,
+            };
+
+            #[allow(clippy::cast_possible_truncation)]
+            let next_instruction = (base + offset + 5) as u32;
+
+            if next_instruction.wrapping_add(operand) == target_u32 {
+                xrefs.push(Xref { kind, address: base + offset });
+            }
+        }
+    }
+
+    xrefs.sort_by_key(|xref| xref.address);
+    xrefs
+}
+
+// Finds the string literal `string` and every reference to it.
+pub fn find_string(memory: &Memory, string: &str) -> Option<(usize, Vec<Xref>)> {
+    let mut literal = string.as_bytes().to_vec();
+    literal.push(0);
+
+    let address = memory.find(&Signature::from_bytes(&literal))?;
+    Some((address, find(memory, address)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find, find_string, Kind, Xref};
+    use crate::resolver::Memory;
+
+    const BASE: usize = 0x0040_0000;
+    const TARGET: usize = BASE + 0x80;
+
+    fn bytes() -> Vec<u8> {
+        let mut bytes = vec![
+            0x68, 0x80, 0x00, 0x40, 0x00,       // 00: push offset aTarget
+            0xBB, 0x80, 0x00, 0x40, 0x00,       // 05: mov ebx,offset aTarget
+            0xBF, 0x80, 0x00, 0x40, 0x00,       // 0A: mov edi,offset aTarget
+            0xB8, 0x80, 0x00, 0x40, 0x00,       // 0F: mov eax,offset aTarget
+            0x8B, 0x35, 0x80, 0x00, 0x40, 0x00, // 14: mov esi,dword ptr ds:[aTarget]
+            0xE8, 0x61, 0x00, 0x00, 0x00,       // 1A: call aTarget
+            0xE9, 0x5C, 0x00, 0x00, 0x00,       // 1F: jmp aTarget
+            0xE8, 0x00, 0x00, 0x00, 0x00,       // 24: call 400029
+            0xB7, 0x80,                         // 29: mov bh,80
+            0x00, 0x40, 0x00,                   // 2B: ...
+            0xC3,                               // 2E: ret
+        ];
+
+        bytes.resize(0x60, 0xCC);
+        bytes.extend_from_slice(&[0x80, 0x00, 0x40, 0x00]);
+        bytes.extend_from_slice(&[0xCC, 0xCC, 0xCC, 0x68]);
+        bytes.extend_from_slice(&[0x80, 0x00, 0x40, 0x00]);
+        bytes.resize(0x80, 0);
+        bytes.extend_from_slice(b"Target\0");
+        bytes
+    }
+
+    fn xref(kind: Kind, offset: usize) -> Xref {
+        Xref { kind, address: BASE + offset }
+    }
+
+    #[test]
+    fn finds_every_kind() {
+        let bytes = bytes();
+        let memory = Memory::new(BASE, &bytes).with_code(Some(BASE..BASE + 0x60));
+
+        assert_eq!(find(&memory, TARGET), [
+            xref(Kind::Push, 0x00),
+            xref(Kind::Mov, 0x05),
+            xref(Kind::Mov, 0x0A),
+            xref(Kind::Mov, 0x0F),
+            xref(Kind::Operand, 0x16),
+            xref(Kind::Call, 0x1A),
+            xref(Kind::Jmp, 0x1F),
+
+            // B7 is `mov r8, imm8`, so the address after it is an operand of something else.
+            xref(Kind::Operand, 0x2A),
+
+            // Outside of the code, even a pointer after a push opcode is only a pointer.
+            xref(Kind::Pointer, 0x60),
+            xref(Kind::Pointer, 0x68),
+        ]);
+    }
+
+    // Every B8+r opcode is a `mov r32, imm32`.
+    #[test]
+    fn mov_covers_every_register() {
+        for opcode in 0xB0..=0xC0_u8 {
+            let mut bytes = vec![opcode, 0x80, 0x00, 0x40, 0x00];
+            bytes.resize(0x80, 0xCC);
+            let memory = Memory::new(BASE, &bytes);
+
+            let kind = if (0xB8..=0xBF).contains(&opcode) { Kind::Mov } else { Kind::Operand };
+            let address = if kind == Kind::Mov { BASE } else { BASE + 1 };
+            assert_eq!(find(&memory, TARGET), [Xref { kind, address }], "{opcode:02X}");
+        }
+    }
+
+    #[test]
+    fn finds_branches_backwards() {
+        let mut bytes = vec![0xCC; 0x10];
+        bytes.extend_from_slice(&[0xE8, 0xEB, 0xFF, 0xFF, 0xFF]); // 10: call 400000
+        bytes.extend_from_slice(&[0xE9, 0xE6, 0xFF, 0xFF, 0xFF]); // 15: jmp 400000
+        let memory = Memory::new(BASE, &bytes);
+
+        assert_eq!(find(&memory, BASE), [xref(Kind::Call, 0x10), xref(Kind::Jmp, 0x15)]);
+    }
+
+    #[test]
+    fn finds_strings() {
+        let bytes = bytes();
+        let memory = Memory::new(BASE, &bytes).with_code(Some(BASE..BASE + 0x60));
+
+        let (address, xrefs) = find_string(&memory, "Target").unwrap();
+        assert_eq!(address, TARGET);
+        assert_eq!(xrefs, find(&memory, TARGET));
+
+        // The nul keeps a prefix from matching.
+        assert_eq!(find_string(&memory, "Targ"), None);
+        assert_eq!(find_string(&memory, "Missing"), None);
+    }
+}
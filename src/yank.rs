@@ -1,3 +1,5 @@
+// The caller promises that the value is there, e.g. an `Option` that we fill before anything reads it.
+#[allow(clippy::missing_safety_doc)]
 pub unsafe trait Yank<T> {
     unsafe fn yank(self) -> T;
     unsafe fn yank_ref(&self) -> &T;